@@ -0,0 +1,122 @@
+use crate::safety::normalize;
+use std::path::{Component, Path, PathBuf};
+
+/// What a configured CMake or Meson build directory says about its source tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildDirInfo {
+    /// "CMake" or "Meson"
+    pub system: &'static str,
+    /// Source directory the build directory was configured for
+    pub source_dir: PathBuf,
+}
+
+impl BuildDirInfo {
+    /// Whether the build was configured for a source tree inside `project_root`
+    pub fn belongs_to(&self, project_root: &Path) -> bool {
+        normalize(&self.source_dir).starts_with(normalize(project_root))
+    }
+}
+
+/// Detect a CMake (`CMakeCache.txt`) or Meson (`meson-info/meson-info.json`) build directory
+pub fn detect(build_dir: &Path) -> Option<BuildDirInfo> {
+    cmake_source_dir(build_dir)
+        .map(|source_dir| BuildDirInfo { system: "CMake", source_dir })
+        .or_else(|| meson_source_dir(build_dir).map(|source_dir| BuildDirInfo { system: "Meson", source_dir }))
+}
+
+fn cmake_source_dir(build_dir: &Path) -> Option<PathBuf> {
+    let cache = std::fs::read_to_string(build_dir.join("CMakeCache.txt")).ok()?;
+    cache.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.split(':').next()? == "CMAKE_HOME_DIRECTORY").then(|| PathBuf::from(value.trim()))
+    })
+}
+
+fn meson_source_dir(build_dir: &Path) -> Option<PathBuf> {
+    let info = std::fs::read_to_string(build_dir.join("meson-info").join("meson-info.json")).ok()?;
+    let info: serde_json::Value = serde_json::from_str(&info).ok()?;
+    info["directories"]["source"].as_str().map(PathBuf::from)
+}
+
+/// Express `dir` relative to `project_root` as stored in `clean.dir`
+///
+/// Returns "." for the root itself and a plain relative path below it.
+/// Directories outside the root are written with leading `..` components
+/// when they share more than the filesystem root with it, and as an
+/// absolute path otherwise.
+pub fn relative_dir(dir: &Path, project_root: &Path) -> String {
+    let dir = normalize(dir);
+    let root = normalize(project_root);
+    if let Ok(relative) = dir.strip_prefix(&root) {
+        return if relative.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            relative.display().to_string()
+        };
+    }
+
+    let common = dir
+        .components()
+        .zip(root.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let shares_only_fs_root = dir
+        .components()
+        .take(common)
+        .all(|c| matches!(c, Component::RootDir | Component::Prefix(_)));
+    if shares_only_fs_root {
+        return dir.display().to_string();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in root.components().skip(common) {
+        relative.push("..");
+    }
+    for component in dir.components().skip(common) {
+        relative.push(component);
+    }
+    relative.display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relative_dir() {
+        let root = Path::new("/work/src");
+        assert_eq!(relative_dir(Path::new("/work/src"), root), ".");
+        assert_eq!(relative_dir(Path::new("/work/src/build"), root), "build");
+        assert_eq!(relative_dir(Path::new("/work/build"), root), "../build");
+        assert_eq!(relative_dir(Path::new("/work/out/debug"), root), "../out/debug");
+        assert_eq!(relative_dir(Path::new("/tmp/build"), root), "/tmp/build");
+    }
+
+    #[test]
+    fn test_detect_cmake_and_meson() {
+        let temp_dir = TempDir::new().unwrap();
+        let cmake = temp_dir.path().join("cmake");
+        std::fs::create_dir(&cmake).unwrap();
+        std::fs::write(
+            cmake.join("CMakeCache.txt"),
+            "# comment\nCMAKE_BUILD_TYPE:STRING=Debug\nCMAKE_HOME_DIRECTORY:INTERNAL=/work/src\n",
+        )
+        .unwrap();
+        let info = detect(&cmake).unwrap();
+        assert_eq!(info, BuildDirInfo { system: "CMake", source_dir: PathBuf::from("/work/src") });
+        assert!(info.belongs_to(Path::new("/work")));
+        assert!(!info.belongs_to(Path::new("/other")));
+
+        let meson = temp_dir.path().join("meson");
+        std::fs::create_dir_all(meson.join("meson-info")).unwrap();
+        std::fs::write(
+            meson.join("meson-info").join("meson-info.json"),
+            r#"{"directories": {"source": "/work/src", "build": "/work/build"}}"#,
+        )
+        .unwrap();
+        assert_eq!(detect(&meson).unwrap().system, "Meson");
+
+        assert!(detect(temp_dir.path()).is_none());
+    }
+}
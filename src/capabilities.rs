@@ -0,0 +1,263 @@
+use crate::error::{Error, Result};
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::subprocess::run_with_timeout;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Options of `c2rust-config config` that c2rust-clean relies on
+const REQUIRED_FLAGS: [&str; 4] = ["--make", "--feature", "--set", "--get"];
+
+/// What an installed c2rust-config supports, as far as its help output tells
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Version reported by `--version`, if any
+    pub version: Option<String>,
+    /// Whether `--help` lists a `config` subcommand; `None` when it lists nothing
+    pub config_subcommand: Option<bool>,
+    /// Long options listed by `config --help`
+    pub config_flags: Vec<String>,
+}
+
+impl Capabilities {
+    /// Whether `config` accepts `flag`; `None` when its help lists no options at all
+    pub fn supports(&self, flag: &str) -> Option<bool> {
+        if self.config_flags.is_empty() {
+            return None;
+        }
+        Some(self.config_flags.iter().any(|f| f == flag))
+    }
+
+    /// Fail with [`Error::ConfigToolIncompatible`] when a required feature is known to be missing
+    ///
+    /// Help output that lists nothing is given the benefit of the doubt.
+    pub fn check_compatible(&self, program: &Path) -> Result<()> {
+        let version = self.version.as_deref().unwrap_or("unknown version");
+        if self.config_subcommand == Some(false) {
+            return Err(Error::ConfigToolIncompatible(format!(
+                "{} ({}) has no `config` subcommand. Install a c2rust-config that provides \
+                 `c2rust-config config`, or use --config-backend native",
+                program.display(),
+                version
+            )));
+        }
+        let missing: Vec<&str> = REQUIRED_FLAGS
+            .iter()
+            .copied()
+            .filter(|flag| self.supports(flag) == Some(false))
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::ConfigToolIncompatible(format!(
+                "{} ({}) does not support `config {}`. Upgrade c2rust-config, or use --config-backend native",
+                program.display(),
+                version,
+                missing.join("`, `config ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Probe `program`, reusing the cached result while the binary is unchanged
+///
+/// Only probes through a runner that [starts real
+/// processes](CommandRunner::starts_real_processes) are cached, so scripted
+/// answers never stand in for the real binary. Returns the resolved path of the binary with its capabilities. Fails with
+/// [`Error::ConfigToolNotFound`] when the binary cannot be found or its
+/// `--help` fails, and with [`Error::ConfigToolFailed`] when a probe runs
+/// longer than `timeout`; a timed out probe is not cached.
+pub fn probe(program: &Path, timeout: Duration, runner: &dyn CommandRunner) -> Result<(PathBuf, Capabilities)> {
+    let path = resolve_program(program).ok_or(Error::ConfigToolNotFound)?;
+    let mtime = modified(&path).ok_or(Error::ConfigToolNotFound)?;
+    if !runner.starts_real_processes() {
+        return Ok((path.clone(), run_probe(&path, timeout, runner)?));
+    }
+
+    let mut cache = ProbeCache::load();
+    let key = path.display().to_string();
+    if let Some(entry) = cache.entries.get(&key).filter(|entry| entry.mtime == mtime) {
+        return Ok((path, entry.capabilities.clone()));
+    }
+
+    let capabilities = run_probe(&path, timeout, runner)?;
+    cache.entries.retain(|path, _| Path::new(path).exists());
+    cache.entries.insert(
+        key,
+        CacheEntry {
+            mtime,
+            capabilities: capabilities.clone(),
+        },
+    );
+    cache.save();
+    Ok((path, capabilities))
+}
+
+fn run_probe(path: &Path, timeout: Duration, runner: &dyn CommandRunner) -> Result<Capabilities> {
+    let run = |args: &[&str]| -> Result<Option<String>> {
+        let mut argv = vec![path.display().to_string()];
+        argv.extend(args.iter().map(|arg| arg.to_string()));
+        let run = run_with_timeout(runner, CommandSpec::new(argv), Some(timeout)).map_err(|_| Error::ConfigToolNotFound)?;
+        if run.timed_out() {
+            return Err(Error::ConfigToolFailed(run.to_string()));
+        }
+        Ok(run.success().then(|| run.stdout_text()))
+    };
+
+    let help = run(&["--help"])?.ok_or(Error::ConfigToolNotFound)?;
+    let version = run(&["--version"])?.and_then(|output| parse_version(&output));
+    let config_help = run(&["config", "--help"])?.unwrap_or_default();
+
+    Ok(Capabilities {
+        version,
+        config_subcommand: (!help.trim().is_empty())
+            .then(|| help.split(|c: char| !c.is_alphanumeric() && c != '-').any(|word| word == "config")),
+        config_flags: parse_long_flags(&config_help),
+    })
+}
+
+/// The first word of `--version` output that starts with a digit, e.g. "0.3.1"
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|word| word.trim_start_matches('v'))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(String::from)
+}
+
+/// Every `--long-option` mentioned in help text, sorted and deduplicated
+fn parse_long_flags(help: &str) -> Vec<String> {
+    let mut flags: Vec<String> = help
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']' | '=' | '<' | '|'))
+        .filter(|word| word.starts_with("--") && word.len() > 2)
+        .map(|word| word.trim_end_matches(|c: char| !c.is_alphanumeric()).to_string())
+        .collect();
+    flags.sort();
+    flags.dedup();
+    flags
+}
+
+/// Find `program` the way `Command` does: as a path when it has more than
+/// one component, on `PATH` otherwise
+pub fn resolve_program(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return program.canonicalize().ok().filter(|p| p.is_file());
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+        .and_then(|found| found.canonicalize().ok())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Modification time as (seconds, nanoseconds) since the Unix epoch
+fn modified(path: &Path) -> Option<(u64, u32)> {
+    let since_epoch = path.metadata().ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProbeCache {
+    /// Keyed by the resolved path of the binary
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    mtime: (u64, u32),
+    capabilities: Capabilities,
+}
+
+/// `$XDG_CACHE_HOME/c2rust-clean/config-tool.json`, falling back to `~/.cache`
+fn cache_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("c2rust-clean").join("config-tool.json"))
+}
+
+impl ProbeCache {
+    fn load() -> Self {
+        cache_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Best effort: a cache that cannot be written only costs another probe
+    fn save(&self) {
+        let Some(path) = cache_path() else {
+            return;
+        };
+        let Ok(json) = serde_json::to_string_pretty(self) else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let temp = path.with_extension(format!("json.{}", std::process::id()));
+        if std::fs::write(&temp, json + "\n").is_ok() {
+            let _ = std::fs::rename(&temp, &path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG_HELP: &str = "Usage: c2rust-config config [OPTIONS]\n\n\
+                               Options:\n  --make            Use the make section\n  \
+                               --feature <NAME>  Feature name\n  --set <KEY> <VALUE>...\n  \
+                               --get <KEY>\n  -h, --help        Print help\n";
+
+    #[test]
+    fn test_parse_help_and_version() {
+        assert_eq!(parse_version("c2rust-config 0.3.1\n").as_deref(), Some("0.3.1"));
+        assert_eq!(parse_version("c2rust-config v1.0\n").as_deref(), Some("1.0"));
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_long_flags(CONFIG_HELP), vec!["--feature", "--get", "--help", "--make", "--set"]);
+    }
+
+    #[test]
+    fn test_check_compatible() {
+        let program = Path::new("/opt/bin/c2rust-config");
+        let compatible = Capabilities {
+            version: Some("0.3.1".to_string()),
+            config_subcommand: Some(true),
+            config_flags: parse_long_flags(CONFIG_HELP),
+        };
+        assert!(compatible.check_compatible(program).is_ok());
+        assert_eq!(compatible.supports("--unset"), Some(false));
+
+        // Silent help output is not held against the tool
+        assert!(Capabilities::default().check_compatible(program).is_ok());
+        assert_eq!(Capabilities::default().supports("--unset"), None);
+
+        let old = Capabilities {
+            version: Some("0.1.0".to_string()),
+            config_subcommand: Some(true),
+            config_flags: vec!["--get".to_string(), "--set".to_string()],
+        };
+        let message = old.check_compatible(program).unwrap_err().to_string();
+        assert!(message.contains("(0.1.0) does not support `config --make`, `config --feature`"), "{}", message);
+
+        let no_config = Capabilities {
+            config_subcommand: Some(false),
+            ..Default::default()
+        };
+        assert!(matches!(no_config.check_compatible(program), Err(Error::ConfigToolIncompatible(_))));
+    }
+}
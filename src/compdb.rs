@@ -0,0 +1,122 @@
+use crate::error::{Error, Result};
+use crate::steps::split_command;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// File name of a compilation database
+pub const COMPDB_FILE: &str = "compile_commands.json";
+
+/// One entry of `compile_commands.json`
+#[derive(Debug, Deserialize)]
+struct CompileCommand {
+    directory: PathBuf,
+    file: PathBuf,
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    arguments: Option<Vec<String>>,
+    #[serde(default)]
+    output: Option<PathBuf>,
+}
+
+impl CompileCommand {
+    /// The file this compilation writes, if it can be determined
+    ///
+    /// Uses the `output` field when present, otherwise the `-o` argument. A
+    /// plain `-c` compile without `-o` writes `<stem>.o` into `directory`.
+    fn output(&self) -> Result<Option<PathBuf>> {
+        if let Some(output) = &self.output {
+            return Ok(Some(self.directory.join(output)));
+        }
+
+        let args = match (&self.arguments, &self.command) {
+            (Some(arguments), _) => arguments.clone(),
+            (None, Some(command)) => split_command(command)?,
+            (None, None) => return Ok(None),
+        };
+
+        let mut compile_only = false;
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            if arg == "-o" {
+                return Ok(iter.next().map(|out| self.directory.join(out)));
+            }
+            if let Some(out) = arg.strip_prefix("-o") {
+                return Ok(Some(self.directory.join(out)));
+            }
+            compile_only |= arg == "-c";
+        }
+
+        Ok(compile_only
+            .then(|| self.file.file_stem().map(|stem| self.directory.join(stem).with_extension("o")))
+            .flatten())
+    }
+}
+
+/// Every output file listed in the compilation database at `path`, sorted and deduplicated
+pub fn load_outputs(path: &Path) -> Result<Vec<PathBuf>> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        Error::InvalidArgument(format!("cannot read compilation database {}: {}", path.display(), e))
+    })?;
+    let commands: Vec<CompileCommand> = serde_json::from_str(&content).map_err(|e| {
+        Error::InvalidArgument(format!("invalid compilation database {}: {}", path.display(), e))
+    })?;
+
+    let mut outputs = Vec::new();
+    for command in &commands {
+        if let Some(output) = command.output()? {
+            outputs.push(crate::safety::normalize(&output));
+        }
+    }
+    outputs.sort();
+    outputs.dedup();
+    Ok(outputs)
+}
+
+/// Outputs from `outputs` that exist on disk
+pub fn existing(outputs: &[PathBuf]) -> Vec<PathBuf> {
+    outputs.iter().filter(|p| p.exists()).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_outputs() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(COMPDB_FILE);
+        std::fs::write(
+            &path,
+            r#"[
+                {"directory": "/p/build", "file": "../src/a.c", "command": "cc -c -o obj/a.o ../src/a.c"},
+                {"directory": "/p/build", "file": "../src/b.c", "arguments": ["cc", "-c", "-oobj/b.o", "../src/b.c"]},
+                {"directory": "/p/build", "file": "../src/c.c", "arguments": ["cc", "-c", "../src/c.c"]},
+                {"directory": "/p/build", "file": "d.c", "arguments": ["cc", "d.c"], "output": "d.out"},
+                {"directory": "/p/build", "file": "../src/a.c", "command": "cc -c -o obj/a.o ../src/a.c"}
+            ]"#,
+        )
+        .unwrap();
+
+        let outputs = load_outputs(&path).unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                PathBuf::from("/p/build/c.o"),
+                PathBuf::from("/p/build/d.out"),
+                PathBuf::from("/p/build/obj/a.o"),
+                PathBuf::from("/p/build/obj/b.o"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_outputs_invalid() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(COMPDB_FILE);
+        assert!(matches!(load_outputs(&path), Err(Error::InvalidArgument(_))));
+        std::fs::write(&path, "{}").unwrap();
+        assert!(matches!(load_outputs(&path), Err(Error::InvalidArgument(_))));
+    }
+}
@@ -1,111 +1,618 @@
+use crate::capabilities;
+use crate::config_store::{ConfigEntry, ConfigStore};
 use crate::error::{Error, Result};
-use std::path::Path;
-use std::process::Command;
+use crate::limits::ResourceLimits;
+use crate::steps::{join_command, CleanStep};
+use crate::runner::{CommandRunner, CommandSpec, Runner};
+use crate::subprocess::{run_with_timeout, ToolRun};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-/// Get the c2rust-config binary path from environment or use default
-/// 
-/// Returns the path to the c2rust-config binary. If the C2RUST_CONFIG
-/// environment variable is set, its value is used. Otherwise, defaults
-/// to "c2rust-config" which relies on PATH resolution.
-fn get_c2rust_config_path() -> String {
-    std::env::var("C2RUST_CONFIG").unwrap_or_else(|_| "c2rust-config".to_string())
+/// File name of the c2rust-config binary
+const CONFIG_TOOL_NAME: &str = "c2rust-config";
+
+/// How long a single c2rust-config call may take unless `--config-timeout` says otherwise
+pub const DEFAULT_CONFIG_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How c2rust-config is found and run
+#[derive(Debug, Clone)]
+pub struct ConfigToolOptions {
+    /// The `--config-tool` path, if any
+    pub path: Option<PathBuf>,
+    /// Time limit of a single call
+    pub timeout: Duration,
+    pub runner: Runner,
+}
+
+impl Default for ConfigToolOptions {
+    fn default() -> Self {
+        ConfigToolOptions {
+            path: None,
+            timeout: DEFAULT_CONFIG_TIMEOUT,
+            runner: Runner::default(),
+        }
+    }
+}
+
+/// Where a c2rust-config candidate comes from, in resolution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolSource {
+    /// The `C2RUST_CONFIG` environment variable
+    EnvVar,
+    /// The `--config-tool` option
+    Flag,
+    /// The directory of the running c2rust-clean binary
+    Sibling,
+    /// `<project_root>/.c2rust/bin`
+    ProjectBin,
+    /// A `PATH` lookup
+    Path,
+}
+
+impl std::fmt::Display for ToolSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ToolSource::EnvVar => "C2RUST_CONFIG",
+            ToolSource::Flag => "--config-tool",
+            ToolSource::Sibling => "next to c2rust-clean",
+            ToolSource::ProjectBin => ".c2rust/bin",
+            ToolSource::Path => "PATH",
+        })
+    }
+}
+
+/// One place c2rust-config was looked for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCandidate {
+    pub source: ToolSource,
+    /// `None` when the source is not configured (unset variable, no flag, no PATH match)
+    pub path: Option<PathBuf>,
+    pub found: bool,
 }
 
-/// Check if c2rust-config command exists
+/// The outcome of looking for c2rust-config
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigToolResolution {
+    pub candidates: Vec<ToolCandidate>,
+    /// Index of the candidate in use
+    pub chosen: Option<usize>,
+}
+
+impl ConfigToolResolution {
+    /// The program to run: the chosen candidate, or a bare name that will fail to start
+    pub fn program(&self) -> PathBuf {
+        self.chosen
+            .and_then(|i| self.candidates[i].path.clone())
+            .unwrap_or_else(|| PathBuf::from(CONFIG_TOOL_NAME))
+    }
+
+    /// Whether the tool was named explicitly by `C2RUST_CONFIG` or `--config-tool`
+    pub fn is_explicit(&self) -> bool {
+        self.chosen
+            .is_some_and(|i| matches!(self.candidates[i].source, ToolSource::EnvVar | ToolSource::Flag))
+    }
+
+    /// Every candidate with its status, for `--explain`
+    pub fn explain(&self) -> String {
+        let mut out = String::from("c2rust-config resolution:");
+        for (i, candidate) in self.candidates.iter().enumerate() {
+            let location = match &candidate.path {
+                Some(path) => path.display().to_string(),
+                None => "(not set)".to_string(),
+            };
+            let status = if self.chosen == Some(i) {
+                if candidate.found { "<- used" } else { "<- used, but not found" }
+            } else if candidate.path.is_some() && !candidate.found {
+                "(not found)"
+            } else {
+                ""
+            };
+            let line = format!("  {:<22} {} {}", candidate.source.to_string(), location, status);
+            out.push('\n');
+            out.push_str(line.trim_end());
+        }
+        if self.chosen.is_none() {
+            out.push_str("\n  no c2rust-config found");
+        }
+        out
+    }
+}
+
+/// Look for c2rust-config
+///
+/// Candidates are tried in this order: the `C2RUST_CONFIG` environment
+/// variable, `config_tool` (from `--config-tool`), the directory of the
+/// running c2rust-clean binary, `<project_root>/.c2rust/bin`, and `PATH`.
+/// An explicitly named tool is used even if it does not exist, so a typo
+/// is reported instead of silently falling through to another binary.
+pub fn resolve_c2rust_config(config_tool: Option<&Path>, project_root: Option<&Path>) -> ConfigToolResolution {
+    let binary = format!("{}{}", CONFIG_TOOL_NAME, std::env::consts::EXE_SUFFIX);
+    let candidate = |source, path: Option<PathBuf>| {
+        let found = path.as_deref().is_some_and(Path::is_file);
+        ToolCandidate { source, path, found }
+    };
+
+    let candidates = vec![
+        candidate(ToolSource::EnvVar, std::env::var_os("C2RUST_CONFIG").filter(|v| !v.is_empty()).map(PathBuf::from)),
+        candidate(ToolSource::Flag, config_tool.map(Path::to_path_buf)),
+        candidate(
+            ToolSource::Sibling,
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|dir| dir.join(&binary))),
+        ),
+        candidate(
+            ToolSource::ProjectBin,
+            project_root.map(|root| root.join(".c2rust").join("bin").join(&binary)),
+        ),
+        candidate(ToolSource::Path, capabilities::resolve_program(Path::new(CONFIG_TOOL_NAME))),
+    ];
+
+    let chosen = candidates.iter().position(|c| match c.source {
+        ToolSource::EnvVar | ToolSource::Flag => c.path.is_some(),
+        _ => c.found,
+    });
+    ConfigToolResolution { candidates, chosen }
+}
+
+/// Check that `program` is a compatible c2rust-config
 /// 
-/// Attempts to execute `c2rust-config --help` to verify the tool is
-/// available and accessible. This should be called before any operations
-/// that depend on c2rust-config.
+/// Probes the tool's `--help`, `--version` and `config --help` output (see
+/// [`capabilities::probe`]; the result is cached while the binary is
+/// unchanged). This should be called before any operations that depend on
+/// c2rust-config.
 /// 
 /// # Returns
 /// 
-/// Returns `Ok(())` if c2rust-config is available, or `Err(Error::ConfigToolNotFound)`
-/// if the tool cannot be found or executed.
-pub fn check_c2rust_config_exists() -> Result<()> {
-    let config_path = get_c2rust_config_path();
-    Command::new(&config_path)
-        .arg("--help")
-        .output()
-        .ok()
-        .filter(|output| output.status.success())
-        .map(|_| ())
-        .ok_or(Error::ConfigToolNotFound)
-}
-
-/// Save clean configuration using c2rust-config
+/// Returns `Ok(())` if c2rust-config is available, `Err(Error::ConfigToolNotFound)`
+/// if the tool cannot be found or executed, `Err(Error::ConfigToolFailed)` if a
+/// probe takes longer than `timeout`, or `Err(Error::ConfigToolIncompatible)`
+/// if it lacks an option c2rust-clean needs.
+pub fn check_config_tool(program: &Path, timeout: Duration, runner: &dyn CommandRunner) -> Result<()> {
+    let (path, capabilities) = capabilities::probe(program, timeout, runner)?;
+    capabilities.check_compatible(&path)
+}
+
+/// The existing [`ConfigStore`]: `c2rust-config config --make` run in the project root
+pub struct SubprocessStore {
+    program: PathBuf,
+    project_root: PathBuf,
+    timeout: Duration,
+    runner: Runner,
+}
+
+impl SubprocessStore {
+    /// Use the c2rust-config found by [`resolve_c2rust_config`] for `project_root`
+    pub fn new(project_root: &Path) -> Self {
+        Self::with_program(&resolve_c2rust_config(None, Some(project_root)).program(), project_root)
+    }
+
+    pub fn with_program(program: &Path, project_root: &Path) -> Self {
+        SubprocessStore {
+            program: program.to_path_buf(),
+            project_root: project_root.to_path_buf(),
+            timeout: DEFAULT_CONFIG_TIMEOUT,
+            runner: Runner::default(),
+        }
+    }
+
+    /// Start c2rust-config through `runner`
+    pub fn with_runner(mut self, runner: Runner) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Kill c2rust-config calls that run longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The c2rust-config binary this store runs
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// `c2rust-config config --make [--feature F] <args...>` in the project root
+    fn command(&self, feature: Option<&str>, args: &[&str]) -> CommandSpec {
+        let mut argv = vec![self.program.display().to_string(), "config".to_string(), "--make".to_string()];
+        if let Some(feature) = feature {
+            argv.extend(["--feature".to_string(), feature.to_string()]);
+        }
+        argv.extend(args.iter().map(|arg| arg.to_string()));
+        CommandSpec::new(argv).in_dir(&self.project_root)
+    }
+
+    /// Run `command`; only a failure to start it or a timeout is an error
+    fn run(&self, command: CommandSpec) -> Result<ToolRun> {
+        let run = run_with_timeout(&*self.runner, command, Some(self.timeout)).map_err(|e| {
+            Error::ConfigToolFailed(format!("failed to execute {}: {}", self.program.display(), e))
+        })?;
+        if run.timed_out() {
+            return Err(Error::ConfigToolFailed(run.to_string()));
+        }
+        Ok(run)
+    }
+}
+
+impl ConfigStore for SubprocessStore {
+    /// Runs `c2rust-config config --make [--feature F] --get <key>` and returns the
+    /// trimmed stdout. A failing lookup (for example an unset key) or empty output
+    /// yields `Ok(None)`.
+    fn get(&self, key: &str, feature: Option<&str>) -> Result<Option<String>> {
+        let run = self.run(self.command(feature, &["--get", key]))?;
+
+        if !run.success() {
+            return Ok(None);
+        }
+
+        let value = run.stdout_text().trim().to_string();
+        Ok(if value.is_empty() { None } else { Some(value) })
+    }
+
+    /// Runs `c2rust-config config --make [--feature F] --set <key> <value>`
+    ///
+    /// Lists are written as a single value with one element per line, so that
+    /// [`get`](Self::get) returns exactly what was set however c2rust-config
+    /// prints arrays, and a rollback can restore them unchanged.
+    fn set(&self, key: &str, values: &[&str], feature: Option<&str>) -> Result<()> {
+        if values.len() > 1 && values.iter().any(|value| value.contains('\n')) {
+            return Err(Error::ConfigSaveFailed(format!(
+                "Failed to save {}: list elements cannot contain line breaks",
+                key
+            )));
+        }
+        let value = values.join("\n");
+        let run = self.run(self.command(feature, &["--set", key, &value])).map_err(|e| Error::ConfigSaveFailed(format!("Failed to save {}: {}", key, e)))?;
+
+        if !run.success() {
+            return Err(Error::ConfigSaveFailed(format!("Failed to save {}: {}", key, run)));
+        }
+
+        Ok(())
+    }
+
+    /// Runs `c2rust-config config --make [--feature F] --unset <key>`
+    fn unset(&self, key: &str, feature: Option<&str>) -> Result<()> {
+        let run = self.run(self.command(feature, &["--unset", key])).map_err(|e| Error::ConfigSaveFailed(format!("Failed to unset {}: {}", key, e)))?;
+
+        if !run.success() {
+            return Err(Error::ConfigSaveFailed(format!("Failed to unset {}: {}", key, run)));
+        }
+
+        Ok(())
+    }
+
+    /// Runs `c2rust-config config --make --list-features`, one feature per line.
+    ///
+    /// `--list-features` is an optional capability: it is only used when
+    /// `config --help` lists it, and yields no features otherwise.
+    fn features(&self) -> Result<Vec<String>> {
+        let supported =
+            capabilities::probe(&self.program, self.timeout, &*self.runner).map(|(_, caps)| caps.supports("--list-features"));
+        if !matches!(supported, Ok(Some(true))) {
+            return Ok(Vec::new());
+        }
+
+        let run = self.run(self.command(None, &["--list-features"]))?;
+
+        if !run.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(run
+            .stdout_text()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+    }
+}
+
+/// Every configuration key owned by c2rust-clean
+pub const CLEAN_KEYS: [&str; 5] = ["clean.dir", "clean.cmd", "clean.steps", "clean.limits", "clean.artifacts"];
+
+/// Check that `name` can be used as a feature name
+///
+/// Names must be non-empty, contain no whitespace or path separators and not
+/// start with `-`, since they are passed to c2rust-config on the command line
+/// and used in file names under `.c2rust/clean/`.
+pub fn validate_feature_name(name: &str) -> Result<()> {
+    let problem = if name.is_empty() {
+        Some("must not be empty")
+    } else if name.chars().any(char::is_whitespace) {
+        Some("must not contain whitespace")
+    } else if name.contains(['/', '\\']) {
+        Some("must not contain path separators")
+    } else if name.starts_with('-') {
+        Some("must not start with '-'")
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(Error::InvalidArgument(format!("feature name '{}' {}", name, problem))),
+        None => Ok(()),
+    }
+}
+
+/// The clean settings saved for one feature
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureConfig {
+    pub feature: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    /// Whether `dir` exists below the project root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir_exists: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+}
+
+impl FeatureConfig {
+    /// Read the `clean.*` keys of `feature` from `store`
+    pub fn load(store: &dyn ConfigStore, feature: &str, project_root: &Path) -> Result<Self> {
+        let dir = store.get("clean.dir", Some(feature))?;
+        let list = |key: &str| -> Result<Vec<String>> {
+            Ok(store
+                .get(key, Some(feature))?
+                .map(|value| value.lines().map(String::from).collect())
+                .unwrap_or_default())
+        };
+        Ok(FeatureConfig {
+            feature: feature.to_string(),
+            dir_exists: dir.as_ref().map(|dir| project_root.join(dir).is_dir()),
+            dir,
+            cmd: store.get("clean.cmd", Some(feature))?,
+            steps: list("clean.steps")?,
+            limits: store.get("clean.limits", Some(feature))?,
+            artifacts: store
+                .get("clean.artifacts", Some(feature))?
+                .map(|value| value.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The settings as entries for [`ConfigStore::set_all`]
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        let mut entries = Vec::new();
+        let mut push = |key: &str, values: Vec<String>| {
+            if !values.is_empty() {
+                entries.push(ConfigEntry { key: key.to_string(), values });
+            }
+        };
+        push("clean.dir", self.dir.iter().cloned().collect());
+        push("clean.cmd", self.cmd.iter().cloned().collect());
+        push("clean.steps", self.steps.clone());
+        push("clean.limits", self.limits.iter().cloned().collect());
+        push("clean.artifacts", self.artifacts.clone());
+        entries
+    }
+
+    /// Whether nothing is saved for the feature
+    pub fn is_empty(&self) -> bool {
+        self.dir.is_none()
+            && self.cmd.is_none()
+            && self.steps.is_empty()
+            && self.limits.is_none()
+            && self.artifacts.is_empty()
+    }
+}
+
+impl std::fmt::Display for FeatureConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Feature '{}':", self.feature)?;
+        if self.is_empty() {
+            return write!(f, "\n  (no clean configuration)");
+        }
+        if let Some(dir) = &self.dir {
+            write!(f, "\n  clean.dir:       {}", dir)?;
+            if self.dir_exists == Some(false) {
+                write!(f, "  ⚠ directory does not exist")?;
+            }
+        }
+        if let Some(cmd) = &self.cmd {
+            write!(f, "\n  clean.cmd:       {}", cmd)?;
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            let label = if i == 0 { "clean.steps:" } else { "" };
+            write!(f, "\n  {:<16} {}", label, step)?;
+        }
+        if let Some(limits) = &self.limits {
+            write!(f, "\n  clean.limits:    {}", limits)?;
+        }
+        if !self.artifacts.is_empty() {
+            write!(f, "\n  clean.artifacts: {}", self.artifacts.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Save the clean configuration
 /// 
-/// Saves the clean directory and command configuration to the project's
-/// c2rust configuration using the c2rust-config tool.
+/// Saves the clean directory and command configuration of the project to
+/// `store`.
 /// 
 /// # Arguments
 /// 
+/// * `store` - The configuration store of the project
 /// * `dir` - The directory path (relative to project root) where the clean command is executed
 /// * `command` - The clean command string to be saved
 /// * `feature` - Optional feature name for the configuration (uses "default" if None)
-/// * `project_root` - The absolute path to the project root directory
 /// 
 /// # Returns
 /// 
 /// Returns `Ok(())` if both `clean.dir` and `clean.cmd` are successfully saved,
-/// or `Err(Error::ConfigSaveFailed)` if the c2rust-config tool fails to save
-/// the configuration.
+/// or `Err(Error::ConfigSaveFailed)` if the store fails to save the
+/// configuration. Both keys are written as one batch, so a failure leaves the
+/// previous values in place.
 /// 
 /// # Example
 /// 
 /// ```no_run
 /// use std::path::Path;
-/// # use c2rust_clean::config_helper::save_config;
+/// # use c2rust_clean::config_helper::{save_config, SubprocessStore};
 /// # use c2rust_clean::error::Result;
 /// # fn example() -> Result<()> {
-/// let project_root = Path::new("/path/to/project");
-/// save_config("src", "make clean", Some("default"), project_root)?;
+/// let store = SubprocessStore::new(Path::new("/path/to/project"));
+/// save_config(&store, "src", "make clean", Some("default"))?;
 /// # Ok(())
 /// # }
 /// ```
-pub fn save_config(dir: &str, command: &str, feature: Option<&str>, project_root: &Path) -> Result<()> {
-    let config_path = get_c2rust_config_path();
-    let feature_args: Vec<&str> = feature.map(|f| vec!["--feature", f]).unwrap_or_default();
+pub fn save_config(store: &dyn ConfigStore, dir: &str, command: &str, feature: Option<&str>) -> Result<()> {
+    store.set_all(&single_command_entries(dir, command), feature)
+}
+
+/// `clean.dir` and `clean.cmd` for a single clean command
+pub fn single_command_entries(dir: &str, command: &str) -> Vec<ConfigEntry> {
+    vec![ConfigEntry::new("clean.dir", &[dir]), ConfigEntry::new("clean.cmd", &[command])]
+}
+
+/// Save a multi-step clean configuration
+///
+/// Every step is stored in order under the list key `clean.steps`, each in the
+/// `DIR:COMMAND` form accepted by `--step`. The first step is also written to
+/// `clean.dir`/`clean.cmd` so tools that only understand a single clean command
+/// keep working.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if all keys are saved, or `Err(Error::ConfigSaveFailed)`
+/// if the store fails for any of them, in which case none of them change.
+pub fn save_steps_config(store: &dyn ConfigStore, steps: &[CleanStep], feature: Option<&str>) -> Result<()> {
+    store.set_all(&steps_entries(steps)?, feature)
+}
+
+/// `clean.dir`/`clean.cmd` of the first step and the `clean.steps` list
+pub fn steps_entries(steps: &[CleanStep]) -> Result<Vec<ConfigEntry>> {
+    let Some(first) = steps.first() else {
+        return Err(Error::ConfigSaveFailed("No clean steps to save".to_string()));
+    };
 
-    // Save both clean.dir and clean.cmd
-    for (key, value) in [("clean.dir", dir), ("clean.cmd", command)] {
-        let output = Command::new(&config_path)
-            .args(&["config", "--make"])
-            .args(&feature_args)
-            .args(&["--set", key, value])
-            .current_dir(project_root)
-            .output()
-            .map_err(|e| Error::ConfigSaveFailed(format!("Failed to execute c2rust-config: {}", e)))?;
+    let mut entries = single_command_entries(&first.dir, &join_command(&first.command));
+    entries.push(ConfigEntry {
+        key: "clean.steps".to_string(),
+        values: steps.iter().map(CleanStep::to_spec).collect(),
+    });
+    Ok(entries)
+}
+
+/// A saved value that differs from the one about to be saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub key: String,
+    pub saved: String,
+    pub new: String,
+}
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::ConfigSaveFailed(format!("Failed to save {}: {}", key, stderr)));
+impl std::fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "  {}:", self.key)?;
+        for line in self.saved.lines() {
+            write!(f, "\n    - {}", line)?;
         }
+        for line in self.new.lines() {
+            write!(f, "\n    + {}", line)?;
+        }
+        Ok(())
     }
+}
 
-    Ok(())
+/// Compare `entries` with the values saved in `store`
+///
+/// Keys without a saved value are not reported, so the first save of a
+/// feature never counts as a change.
+pub fn config_changes(store: &dyn ConfigStore, entries: &[ConfigEntry], feature: Option<&str>) -> Result<Vec<ConfigChange>> {
+    let mut changes = Vec::new();
+    for entry in entries {
+        let new = entry.values.join("\n");
+        if let Some(saved) = store.get(&entry.key, feature)? {
+            // Lists saved by older versions as c2rust-config arrays may be printed
+            // differently, so only the words are compared
+            if !saved.split_whitespace().eq(new.split_whitespace()) {
+                changes.push(ConfigChange {
+                    key: entry.key.clone(),
+                    saved,
+                    new,
+                });
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Save the resource limits for the clean command under `clean.limits`
+pub fn save_limits(store: &dyn ConfigStore, limits: &ResourceLimits, feature: Option<&str>) -> Result<()> {
+    store.set("clean.limits", &[&limits.to_spec()], feature)
+}
+
+/// Load the resource limits saved under `clean.limits`, if any
+pub fn load_limits(store: &dyn ConfigStore, feature: Option<&str>) -> Result<ResourceLimits> {
+    match store.get("clean.limits", feature)? {
+        Some(spec) => ResourceLimits::parse_spec(&spec),
+        None => Ok(ResourceLimits::default()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Mutex;
+
+    /// Serializes tests that modify the C2RUST_CONFIG environment variable
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
 
     #[test]
-    fn test_get_c2rust_config_path_returns_value() {
-        // Just test that it returns a non-empty string
-        let path = get_c2rust_config_path();
-        assert!(!path.is_empty());
-        // If C2RUST_CONFIG is set, it should match that value
-        // Otherwise it should be the default "c2rust-config"
-        if let Ok(env_val) = std::env::var("C2RUST_CONFIG") {
-            assert_eq!(path, env_val);
-        } else {
-            assert_eq!(path, "c2rust-config");
+    fn test_resolve_c2rust_config_order() {
+        use tempfile::TempDir;
+
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let original = std::env::var("C2RUST_CONFIG").ok();
+        std::env::remove_var("C2RUST_CONFIG");
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_bin = temp_dir.path().join(".c2rust").join("bin");
+        std::fs::create_dir_all(&project_bin).unwrap();
+        std::fs::write(project_bin.join(CONFIG_TOOL_NAME), "").unwrap();
+
+        // The project-local tool is found without any configuration
+        let resolution = resolve_c2rust_config(None, Some(temp_dir.path()));
+        let local = resolution.clone();
+        // An explicit --config-tool wins over it, even when it does not exist
+        let flagged = resolve_c2rust_config(Some(Path::new("/opt/missing/c2rust-config")), Some(temp_dir.path()));
+        // And C2RUST_CONFIG wins over the flag
+        std::env::set_var("C2RUST_CONFIG", "/env/c2rust-config");
+        let from_env = resolve_c2rust_config(Some(Path::new("/opt/missing/c2rust-config")), None);
+
+        match original {
+            Some(val) => std::env::set_var("C2RUST_CONFIG", val),
+            None => std::env::remove_var("C2RUST_CONFIG"),
         }
+
+        assert_eq!(local.program(), project_bin.join(CONFIG_TOOL_NAME));
+        assert_eq!(local.candidates[local.chosen.unwrap()].source, ToolSource::ProjectBin);
+        assert!(!local.is_explicit());
+        assert!(local.explain().contains("<- used"), "{}", local.explain());
+
+        assert_eq!(flagged.program(), PathBuf::from("/opt/missing/c2rust-config"));
+        assert!(flagged.is_explicit());
+        assert!(flagged.explain().contains("<- used, but not found"), "{}", flagged.explain());
+
+        assert_eq!(from_env.program(), PathBuf::from("/env/c2rust-config"));
+        assert_eq!(from_env.candidates[from_env.chosen.unwrap()].source, ToolSource::EnvVar);
     }
 
     #[test]
-    fn test_check_c2rust_config_exists_with_invalid_path() {
+    fn test_check_config_tool_with_invalid_path() {
         // Save the current C2RUST_CONFIG value to restore after the test
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         let original = std::env::var("C2RUST_CONFIG").ok();
 
         // Point C2RUST_CONFIG to a path that definitely does not exist
@@ -113,7 +620,7 @@ mod tests {
         std::env::set_var("C2RUST_CONFIG", nonexistent_path);
 
         // Now check that the helper reports the tool as not found
-        let result = check_c2rust_config_exists();
+        let result = check_config_tool(&resolve_c2rust_config(None, None).program(), DEFAULT_CONFIG_TIMEOUT, &*Runner::default());
         match result {
             Err(Error::ConfigToolNotFound) => {}
             other => panic!(
@@ -137,6 +644,7 @@ mod tests {
         use tempfile::TempDir;
 
         // Save the current C2RUST_CONFIG value
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         let original = env::var("C2RUST_CONFIG").ok();
 
         // Create a temp directory and mock c2rust-config script
@@ -171,7 +679,7 @@ mod tests {
 
         // Test save_config
         let project_root = temp_dir.path();
-        let result = save_config("src", "make clean", Some("default"), project_root);
+        let result = save_config(&SubprocessStore::new(project_root), "src", "make clean", Some("default"));
 
         // Should succeed
         assert!(result.is_ok(), "Expected save_config to succeed, got: {:?}", result);
@@ -183,6 +691,102 @@ mod tests {
         }
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_get_config_value_and_limits() {
+        use std::fs;
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mock_script_path = temp_dir.path().join("mock-c2rust-config-get");
+        let mut script = fs::File::create(&mock_script_path).unwrap();
+        writeln!(script, "#!/bin/bash").unwrap();
+        writeln!(script, "if [ \"${{@: -1}}\" = \"clean.limits\" ]; then echo 'cpu=30 nofile=64'; exit 0; fi").unwrap();
+        writeln!(script, "exit 1").unwrap();
+        drop(script);
+        let mut perms = fs::metadata(&mock_script_path).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&mock_script_path, perms).unwrap();
+
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let original = std::env::var("C2RUST_CONFIG").ok();
+        std::env::set_var("C2RUST_CONFIG", &mock_script_path);
+
+        let store = SubprocessStore::new(temp_dir.path());
+        let missing = store.get("clean.cmd", Some("default"));
+        let limits = load_limits(&store, Some("default"));
+
+        match original {
+            Some(val) => std::env::set_var("C2RUST_CONFIG", val),
+            None => std::env::remove_var("C2RUST_CONFIG"),
+        }
+
+        assert_eq!(missing.unwrap(), None);
+        let limits = limits.unwrap();
+        assert_eq!(limits.cpu_secs, Some(30));
+        assert_eq!(limits.open_files, Some(64));
+    }
+
+    #[test]
+    fn test_validate_feature_name() {
+        assert!(validate_feature_name("default").is_ok());
+        assert!(validate_feature_name("release-lto").is_ok());
+        for name in ["", " ", "my feature", "a\tb", "a/b", "--all"] {
+            assert!(
+                matches!(validate_feature_name(name), Err(Error::InvalidArgument(_))),
+                "{:?} should be rejected",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_features_only_when_advertised() {
+        use crate::runner::{RecordingRunner, Response, Runner};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let program = temp_dir.path().join("c2rust-config");
+        std::fs::write(&program, "").unwrap();
+        let features = |config_help: &str| {
+            let recorder = RecordingRunner::new();
+            recorder.respond(&["config", "--help"], Response::success(config_help));
+            recorder.respond(&["--list-features"], Response::success("debug\nrelease\n"));
+            let store = SubprocessStore::with_program(&program, temp_dir.path()).with_runner(Runner::new(recorder.clone()));
+            let listed = store.features().unwrap();
+            let asked = recorder.commands().iter().any(|command| command.argv.iter().any(|arg| arg == "--list-features"));
+            (listed, asked)
+        };
+
+        // Help that lists nothing is not taken as support for the option
+        assert_eq!(features(""), (Vec::new(), false));
+        assert_eq!(features("  --get <KEY>\n  --set <KEY>\n"), (Vec::new(), false));
+        assert_eq!(
+            features("  --get <KEY>\n  --list-features\n"),
+            (vec!["debug".to_string(), "release".to_string()], true)
+        );
+    }
+
+    #[test]
+    fn test_config_changes() {
+        use crate::config_store::NativeStore;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let store = NativeStore::new(temp_dir.path());
+        let entries = single_command_entries("build", "make distclean");
+        assert!(config_changes(&store, &entries, None).unwrap().is_empty());
+
+        store.set_all(&entries, None).unwrap();
+        assert!(config_changes(&store, &entries, None).unwrap().is_empty());
+
+        let changes = config_changes(&store, &single_command_entries("build", "rm -rf build"), None).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), "  clean.cmd:\n    - make distclean\n    + rm -rf build");
+    }
+
     #[test]
     fn test_save_config_failure() {
         use std::env;
@@ -191,6 +795,7 @@ mod tests {
         use tempfile::TempDir;
 
         // Save the current C2RUST_CONFIG value
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         let original = env::var("C2RUST_CONFIG").ok();
 
         // Create a temp directory and mock c2rust-config script that fails
@@ -227,7 +832,7 @@ mod tests {
 
         // Test save_config
         let project_root = temp_dir.path();
-        let result = save_config("src", "make clean", Some("default"), project_root);
+        let result = save_config(&SubprocessStore::new(project_root), "src", "make clean", Some("default"));
 
         // Should fail with ConfigSaveFailed
         match result {
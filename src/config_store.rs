@@ -0,0 +1,518 @@
+use crate::config_helper::{check_config_tool, resolve_c2rust_config, ConfigToolOptions, SubprocessStore};
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Where clean configuration values such as `clean.dir` and `clean.cmd` are stored
+///
+/// Keys are dotted names and values are lists of strings; a single value is
+/// the common case. `feature` defaults to "default" when `None`.
+pub trait ConfigStore {
+    /// Read `key`, joining list values with newlines. Unset keys yield `Ok(None)`.
+    fn get(&self, key: &str, feature: Option<&str>) -> Result<Option<String>>;
+
+    /// Replace the value(s) of `key`
+    fn set(&self, key: &str, values: &[&str], feature: Option<&str>) -> Result<()>;
+
+    /// Remove `key`; removing an unset key is not an error
+    fn unset(&self, key: &str, feature: Option<&str>) -> Result<()>;
+
+    /// Names of the features with stored configuration, as far as the store can tell
+    fn features(&self) -> Result<Vec<String>>;
+
+    /// Write all `entries` or none of them; an entry without values removes its key
+    ///
+    /// The default implementation reads the previous values of all keys first,
+    /// then writes the keys one by one and, when one fails, restores the keys
+    /// already written to their previous values.
+    fn set_all(&self, entries: &[ConfigEntry], feature: Option<&str>) -> Result<()> {
+        let previous_values = entries
+            .iter()
+            .map(|entry| self.get(&entry.key, feature))
+            .collect::<Result<Vec<_>>>()?;
+        let mut written: Vec<(&str, Option<String>)> = Vec::new();
+        for (entry, previous) in entries.iter().zip(previous_values) {
+            let result = if entry.values.is_empty() {
+                self.unset(&entry.key, feature)
+            } else {
+                self.set(&entry.key, &entry.value_refs(), feature)
+            };
+            if let Err(error) = result {
+                return Err(rollback(self, &written, feature, error));
+            }
+            written.push((&entry.key, previous));
+        }
+        Ok(())
+    }
+}
+
+/// A key and the value(s) to store under it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+impl ConfigEntry {
+    pub fn new(key: &str, values: &[&str]) -> Self {
+        ConfigEntry {
+            key: key.to_string(),
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    /// An entry that removes `key` in [`ConfigStore::set_all`]
+    pub fn unset(key: &str) -> Self {
+        ConfigEntry {
+            key: key.to_string(),
+            values: Vec::new(),
+        }
+    }
+
+    fn value_refs(&self) -> Vec<&str> {
+        self.values.iter().map(String::as_str).collect()
+    }
+}
+
+/// Restore the `written` keys (newest first) after `error`, and describe the outcome
+fn rollback<S: ConfigStore + ?Sized>(
+    store: &S,
+    written: &[(&str, Option<String>)],
+    feature: Option<&str>,
+    error: Error,
+) -> Error {
+    if written.is_empty() {
+        return error;
+    }
+    let mut failed = Vec::new();
+    for (key, previous) in written.iter().rev() {
+        let restored = match previous {
+            Some(value) => store.set(key, &value.lines().collect::<Vec<_>>(), feature),
+            None => store.unset(key, feature),
+        };
+        if let Err(e) = restored {
+            failed.push(format!("{} ({})", key, e));
+        }
+    }
+    let keys: Vec<&str> = written.iter().map(|(key, _)| *key).collect();
+    let outcome = if failed.is_empty() {
+        format!("rolled back {}", keys.join(", "))
+    } else {
+        format!("rollback failed for {}, the configuration may be incomplete", failed.join(", "))
+    };
+    let msg = match error {
+        Error::ConfigSaveFailed(msg) => msg,
+        other => other.to_string(),
+    };
+    // Multi-line diagnostics (see `ToolRun`) get the outcome on a line of its own
+    let separator = if msg.trim_end().contains('\n') { "\n" } else { "; " };
+    Error::ConfigSaveFailed(format!("{}{}{}", msg.trim_end(), separator, outcome))
+}
+
+/// Which [`ConfigStore`] to use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigBackend {
+    /// c2rust-config when it is installed, the native file otherwise
+    #[default]
+    Auto,
+    /// Always shell out to c2rust-config
+    C2rustConfig,
+    /// Always use the native file under `.c2rust/`
+    Native,
+}
+
+/// Open the configuration store of `project_root` for `backend`
+///
+/// c2rust-config is looked up as described in [`resolve_c2rust_config`].
+/// `Auto` only falls back to the native store when no c2rust-config is found;
+/// an explicit `C2RUST_CONFIG` or `--config-tool` that does not work, or an
+/// incompatible c2rust-config, is an error. Every c2rust-config call is
+/// killed after `tool.timeout`.
+pub fn open(backend: ConfigBackend, tool: &ConfigToolOptions, project_root: &Path) -> Result<Box<dyn ConfigStore>> {
+    if backend == ConfigBackend::Native {
+        return Ok(Box::new(NativeStore::new(project_root)));
+    }
+    let resolution = resolve_c2rust_config(tool.path.as_deref(), Some(project_root));
+    let program = resolution.program();
+    match (backend, check_config_tool(&program, tool.timeout, &*tool.runner)) {
+        (_, Ok(())) => Ok(Box::new(
+            SubprocessStore::with_program(&program, project_root)
+                .with_timeout(tool.timeout)
+                .with_runner(tool.runner.clone()),
+        )),
+        (ConfigBackend::Auto, Err(Error::ConfigToolNotFound)) if !resolution.is_explicit() => {
+            let store = NativeStore::new(project_root);
+            eprintln!(
+                "Note: c2rust-config not found, using the native configuration file {}",
+                store.path.display()
+            );
+            Ok(Box::new(store))
+        }
+        (_, Err(e)) => Err(e),
+    }
+}
+
+/// Path of the native configuration file
+pub fn native_config_path(project_root: &Path) -> PathBuf {
+    project_root.join(".c2rust").join("clean").join("config.toml")
+}
+
+/// Configuration kept in `.c2rust/clean/config.toml`, without c2rust-config
+///
+/// Each feature is a table and dotted keys are nested tables:
+///
+/// ```toml
+/// [feature.default.clean]
+/// dir = "build"
+/// cmd = "make clean"
+/// ```
+pub struct NativeStore {
+    path: PathBuf,
+}
+
+impl NativeStore {
+    pub fn new(project_root: &Path) -> Self {
+        NativeStore {
+            path: native_config_path(project_root),
+        }
+    }
+
+    fn load(&self) -> Result<Table> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => content.parse::<Table>().map_err(|e| {
+                Error::InvalidArgument(format!("invalid configuration file {}: {}", self.path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Table::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the whole table through a temporary file so readers never see a partial file
+    fn save(&self, table: &Table) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string(table).map_err(|e| Error::ConfigSaveFailed(e.to_string()))?;
+        let temp = self.path.with_extension("toml.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// The path of tables leading to `key` of `feature`
+fn key_path<'a>(key: &'a str, feature: Option<&'a str>) -> Vec<&'a str> {
+    let mut path = vec!["feature", feature.unwrap_or("default")];
+    path.extend(key.split('.'));
+    path
+}
+
+/// Remove the value at `path`, dropping tables left empty. Returns whether anything was removed.
+fn remove_path(table: &mut Table, path: &[&str]) -> bool {
+    match path {
+        [] => false,
+        [name] => table.remove(*name).is_some(),
+        [first, rest @ ..] => {
+            let Some(Value::Table(child)) = table.get_mut(*first) else {
+                return false;
+            };
+            let removed = remove_path(child, rest);
+            if child.is_empty() {
+                table.remove(*first);
+            }
+            removed
+        }
+    }
+}
+
+impl ConfigStore for NativeStore {
+    fn get(&self, key: &str, feature: Option<&str>) -> Result<Option<String>> {
+        let table = self.load()?;
+        let path = key_path(key, feature);
+        let (name, tables) = path.split_last().expect("key path is never empty");
+
+        let mut current = &table;
+        for part in tables {
+            match current.get(*part) {
+                Some(Value::Table(next)) => current = next,
+                _ => return Ok(None),
+            }
+        }
+        let value = match current.get(*name) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| item.as_str().map(String::from).unwrap_or_else(|| item.to_string()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Some(other) => other.to_string(),
+            None => return Ok(None),
+        };
+        Ok(if value.is_empty() { None } else { Some(value) })
+    }
+
+    fn set(&self, key: &str, values: &[&str], feature: Option<&str>) -> Result<()> {
+        self.set_all(&[ConfigEntry::new(key, values)], feature)
+    }
+
+    /// Remove `key`, dropping tables it leaves empty
+    fn unset(&self, key: &str, feature: Option<&str>) -> Result<()> {
+        let mut table = self.load()?;
+        if remove_path(&mut table, &key_path(key, feature)) {
+            self.save(&table)?;
+        }
+        Ok(())
+    }
+
+    fn features(&self) -> Result<Vec<String>> {
+        let table = self.load()?;
+        Ok(match table.get("feature") {
+            Some(Value::Table(features)) => features.keys().cloned().collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// Apply every entry to the loaded table and save it once, so a failure
+    /// leaves the file untouched
+    fn set_all(&self, entries: &[ConfigEntry], feature: Option<&str>) -> Result<()> {
+        let mut table = self.load()?;
+        for entry in entries {
+            if entry.values.is_empty() {
+                remove_path(&mut table, &key_path(&entry.key, feature));
+            } else {
+                self.insert(&mut table, entry, feature)?;
+            }
+        }
+        self.save(&table)
+    }
+}
+
+impl NativeStore {
+    fn insert(&self, table: &mut Table, entry: &ConfigEntry, feature: Option<&str>) -> Result<()> {
+        let key = entry.key.as_str();
+        let path = key_path(key, feature);
+        let (name, tables) = path.split_last().expect("key path is never empty");
+
+        let mut current = table;
+        for part in tables {
+            let slot = current
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            current = match slot {
+                Value::Table(next) => next,
+                _ => {
+                    return Err(Error::ConfigSaveFailed(format!(
+                        "cannot save {}: '{}' in {} is not a table",
+                        key,
+                        part,
+                        self.path.display()
+                    )))
+                }
+            };
+        }
+        let value = match entry.values.as_slice() {
+            [single] => Value::String(single.clone()),
+            values => Value::Array(values.iter().map(|v| Value::String(v.clone())).collect()),
+        };
+        current.insert(name.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_native_store_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = NativeStore::new(temp_dir.path());
+        assert_eq!(store.get("clean.dir", None).unwrap(), None);
+
+        store.set("clean.dir", &["build"], None).unwrap();
+        store.set("clean.steps", &["build:make clean", "doc:make clean"], None).unwrap();
+        store.set("clean.dir", &["other"], Some("debug")).unwrap();
+        assert_eq!(store.features().unwrap(), vec!["debug".to_string(), "default".to_string()]);
+
+        assert_eq!(store.get("clean.dir", None).unwrap().as_deref(), Some("build"));
+        assert_eq!(store.get("clean.dir", Some("default")).unwrap().as_deref(), Some("build"));
+        assert_eq!(store.get("clean.dir", Some("debug")).unwrap().as_deref(), Some("other"));
+        assert_eq!(
+            store.get("clean.steps", None).unwrap().as_deref(),
+            Some("build:make clean\ndoc:make clean")
+        );
+        assert_eq!(store.get("clean.cmd", Some("debug")).unwrap(), None);
+
+        store.unset("clean.dir", Some("debug")).unwrap();
+        assert_eq!(store.features().unwrap(), vec!["default".to_string()]);
+
+        let content = std::fs::read_to_string(native_config_path(temp_dir.path())).unwrap();
+        assert!(content.contains("[feature.default.clean]"), "{}", content);
+    }
+
+    /// In-memory store whose writes to `failing_key` and reads of `unreadable_key` fail
+    struct FlakyStore {
+        values: std::cell::RefCell<std::collections::HashMap<String, String>>,
+        failing_key: &'static str,
+        unreadable_key: &'static str,
+    }
+
+    impl ConfigStore for FlakyStore {
+        fn get(&self, key: &str, _feature: Option<&str>) -> Result<Option<String>> {
+            if key == self.unreadable_key {
+                return Err(Error::ConfigSaveFailed(format!("Failed to read {}: timed out", key)));
+            }
+            Ok(self.values.borrow().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, values: &[&str], _feature: Option<&str>) -> Result<()> {
+            if key == self.failing_key {
+                return Err(Error::ConfigSaveFailed(format!("Failed to save {}: disk full", key)));
+            }
+            self.values.borrow_mut().insert(key.to_string(), values.join("\n"));
+            Ok(())
+        }
+
+        fn unset(&self, key: &str, _feature: Option<&str>) -> Result<()> {
+            self.values.borrow_mut().remove(key);
+            Ok(())
+        }
+
+        fn features(&self) -> Result<Vec<String>> {
+            Ok(vec!["default".to_string()])
+        }
+    }
+
+    #[test]
+    fn test_set_all_rolls_back_on_failure() {
+        let store = FlakyStore {
+            values: Default::default(),
+            failing_key: "clean.steps",
+            unreadable_key: "",
+        };
+        store.set("clean.dir", &["old"], None).unwrap();
+
+        let entries = [
+            ConfigEntry::new("clean.dir", &["build"]),
+            ConfigEntry::new("clean.cmd", &["make clean"]),
+            ConfigEntry::new("clean.steps", &["build:make clean"]),
+        ];
+        let err = store.set_all(&entries, None).unwrap_err();
+        assert!(
+            matches!(&err, Error::ConfigSaveFailed(msg) if msg.contains("rolled back clean.dir, clean.cmd")),
+            "{}",
+            err
+        );
+        assert_eq!(store.get("clean.dir", None).unwrap().as_deref(), Some("old"));
+        assert_eq!(store.get("clean.cmd", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_all_writes_nothing_when_a_read_fails() {
+        let store = FlakyStore {
+            values: Default::default(),
+            failing_key: "",
+            unreadable_key: "clean.cmd",
+        };
+        store.set("clean.dir", &["old"], None).unwrap();
+
+        let entries = [
+            ConfigEntry::new("clean.dir", &["build"]),
+            ConfigEntry::new("clean.cmd", &["make clean"]),
+        ];
+        let err = store.set_all(&entries, None).unwrap_err();
+        assert!(err.to_string().contains("Failed to read clean.cmd"), "{}", err);
+        assert_eq!(store.get("clean.dir", None).unwrap().as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn test_subprocess_set_all_restores_lists() {
+        use crate::runner::{RecordingRunner, Response, Runner};
+
+        let recorder = RecordingRunner::new();
+        recorder.respond(&["--get", "clean.steps"], Response::success("a:make clean\nb:make 'x y'\n"));
+        recorder.respond(&["--get", "clean.dir"], Response::failure(1, "not set"));
+        recorder.respond(&["--get", "clean.artifacts"], Response::failure(1, "not set"));
+        recorder.respond(&["--set", "clean.artifacts"], Response::failure(4, "disk full"));
+        let store = SubprocessStore::with_program(Path::new("c2rust-config"), Path::new("/p"))
+            .with_runner(Runner::new(recorder.clone()));
+
+        let entries = [
+            ConfigEntry::new("clean.dir", &["."]),
+            ConfigEntry::new("clean.steps", &[".:make clean", "doc:make clean"]),
+            ConfigEntry::new("clean.artifacts", &["*.o"]),
+        ];
+        let err = store.set_all(&entries, None).unwrap_err();
+        assert!(err.to_string().contains("rolled back clean.dir, clean.steps"), "{}", err);
+
+        let writes: Vec<Vec<String>> = recorder
+            .commands()
+            .into_iter()
+            .map(|command| command.argv[3..].to_vec())
+            .filter(|args| args[0] != "--get")
+            .collect();
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            writes,
+            vec![
+                args(&["--set", "clean.dir", "."]),
+                args(&["--set", "clean.steps", ".:make clean\ndoc:make clean"]),
+                args(&["--set", "clean.artifacts", "*.o"]),
+                // The list comes back exactly as it was, element for element
+                args(&["--set", "clean.steps", "a:make clean\nb:make 'x y'"]),
+                args(&["--unset", "clean.dir"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_all_unsets_in_the_same_batch() {
+        let store = FlakyStore {
+            values: Default::default(),
+            failing_key: "clean.artifacts",
+            unreadable_key: "",
+        };
+        store.set("clean.steps", &["a:make clean"], None).unwrap();
+
+        // A failing write restores the key removed earlier in the batch
+        let entries = [ConfigEntry::unset("clean.steps"), ConfigEntry::new("clean.artifacts", &["*.o"])];
+        assert!(store.set_all(&entries, None).is_err());
+        assert_eq!(store.get("clean.steps", None).unwrap().as_deref(), Some("a:make clean"));
+
+        let temp_dir = TempDir::new().unwrap();
+        let native = NativeStore::new(temp_dir.path());
+        native.set("clean.steps", &["a:make clean"], None).unwrap();
+        let entries = [ConfigEntry::unset("clean.steps"), ConfigEntry::new("clean.cmd", &["make clean"])];
+        native.set_all(&entries, None).unwrap();
+        assert_eq!(native.get("clean.steps", None).unwrap(), None);
+        assert_eq!(native.get("clean.cmd", None).unwrap().as_deref(), Some("make clean"));
+    }
+
+    #[test]
+    fn test_native_set_all_is_atomic() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = NativeStore::new(temp_dir.path());
+        store.set("clean.dir", &["old"], None).unwrap();
+
+        // `clean.dir` cannot become a table, so the whole batch is rejected
+        let entries = [
+            ConfigEntry::new("clean.cmd", &["make clean"]),
+            ConfigEntry::new("clean.dir.sub", &["x"]),
+        ];
+        assert!(store.set_all(&entries, None).is_err());
+        assert_eq!(store.get("clean.dir", None).unwrap().as_deref(), Some("old"));
+        assert_eq!(store.get("clean.cmd", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_native_store_invalid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = native_config_path(temp_dir.path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "not = [valid").unwrap();
+
+        let store = NativeStore::new(temp_dir.path());
+        assert!(matches!(store.get("clean.dir", None), Err(Error::InvalidArgument(_))));
+    }
+}
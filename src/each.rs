@@ -0,0 +1,191 @@
+use crate::error::{Error, Result};
+use crate::executor::{self, CapturedRun, ExecOptions};
+use crate::limits::ResourceLimits;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A directory selected by `--each`, with its project root already resolved
+#[derive(Debug, Clone)]
+pub struct DirTarget {
+    /// Absolute directory the clean command runs in
+    pub dir: PathBuf,
+    /// Project root detected for `dir`
+    pub project_root: PathBuf,
+    /// `dir` relative to `project_root`, as saved in `clean.dir`
+    pub relative: String,
+    /// Resource limits for this directory, replacing those in the shared options
+    pub limits: ResourceLimits,
+}
+
+/// Result of running the clean command in one directory
+#[derive(Debug)]
+pub struct DirOutcome {
+    pub target: DirTarget,
+    pub result: Result<CapturedRun>,
+}
+
+impl DirOutcome {
+    pub fn succeeded(&self) -> bool {
+        matches!(&self.result, Ok(run) if run.status.success())
+    }
+}
+
+/// Expand `pattern` (relative to `base`) into the list of matching directories
+///
+/// Matches that are not directories are ignored. The result is sorted so the
+/// summary order is stable between runs.
+pub fn expand_dirs(pattern: &str, base: &Path) -> Result<Vec<PathBuf>> {
+    let full_pattern = if Path::new(pattern).is_absolute() {
+        pattern.to_string()
+    } else {
+        base.join(pattern).display().to_string()
+    };
+
+    let paths = glob::glob(&full_pattern)
+        .map_err(|e| Error::InvalidArgument(format!("invalid --each pattern '{}': {}", pattern, e)))?;
+
+    let mut dirs: Vec<PathBuf> = paths
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+
+    if dirs.is_empty() {
+        return Err(Error::InvalidArgument(format!(
+            "--each pattern '{}' did not match any directory",
+            pattern
+        )));
+    }
+
+    Ok(dirs)
+}
+
+/// Run `command` in every target using at most `jobs` concurrent processes
+///
+/// Each directory's output is captured and printed as one block once that
+/// directory finishes, so output from different directories never interleaves.
+/// Outcomes are returned in the same order as `targets`.
+pub fn run_each(targets: Vec<DirTarget>, command: &[String], options: &ExecOptions, jobs: usize) -> Vec<DirOutcome> {
+    let total = targets.len();
+    let queue = Mutex::new(targets.into_iter().enumerate().collect::<VecDeque<_>>());
+    let results = Mutex::new(Vec::with_capacity(total));
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, total.max(1)) {
+            scope.spawn(|| loop {
+                let Some((index, target)) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+                let options = ExecOptions {
+                    limits: target.limits.clone(),
+                    ..options.clone()
+                };
+                let result = executor::capture_command(&target.dir, command, &options);
+                let outcome = DirOutcome { target, result };
+                print_group(&outcome);
+                results.lock().unwrap().push((index, outcome));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+/// Print one directory's captured output as a single block
+fn print_group(outcome: &DirOutcome) {
+    let mut out = std::io::stdout().lock();
+    let dir = outcome.target.dir.display();
+    // Output is best-effort; a closed stdout must not abort the other jobs
+    let _ = match &outcome.result {
+        Ok(run) => {
+            let status = match run.status.code() {
+                Some(code) => format!("exit code {}", code),
+                None => "terminated by signal".to_string(),
+            };
+            writeln!(out, "==> {} ({})", dir, status)
+                .and_then(|_| out.write_all(&run.output))
+        }
+        Err(e) => writeln!(out, "==> {} (failed to run)\n{}", dir, e),
+    };
+    let _ = writeln!(out);
+}
+
+/// Print the aggregated pass/fail summary
+pub fn print_summary(outcomes: &[DirOutcome]) {
+    let passed = outcomes.iter().filter(|o| o.succeeded()).count();
+    println!("Summary: {} passed, {} failed", passed, outcomes.len() - passed);
+    for outcome in outcomes {
+        let dir = outcome.target.dir.display();
+        match &outcome.result {
+            Ok(run) if run.status.success() => println!("  ✓ {} ({})", dir, run.stats),
+            Ok(run) => println!(
+                "  ✗ {} (exit code {}, {})",
+                dir,
+                run.status.code().unwrap_or(-1),
+                run.stats
+            ),
+            Err(e) => println!("  ✗ {} ({})", dir, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_expand_dirs_only_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("liba")).unwrap();
+        fs::create_dir(temp_dir.path().join("libb")).unwrap();
+        fs::write(temp_dir.path().join("libc.txt"), "").unwrap();
+
+        let dirs = expand_dirs("lib*", temp_dir.path()).unwrap();
+        assert_eq!(dirs, vec![temp_dir.path().join("liba"), temp_dir.path().join("libb")]);
+    }
+
+    #[test]
+    fn test_expand_dirs_no_match() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(matches!(expand_dirs("missing*", temp_dir.path()), Err(Error::InvalidArgument(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_each_keeps_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let targets: Vec<DirTarget> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let dir = temp_dir.path().join(name);
+                fs::create_dir(&dir).unwrap();
+                DirTarget {
+                    dir,
+                    project_root: temp_dir.path().to_path_buf(),
+                    relative: name.to_string(),
+                    // Each directory gets its own limit
+                    limits: ResourceLimits {
+                        open_files: Some(if *name == "c" { 64 } else { 128 }),
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect();
+
+        let command = ["sh".to_string(), "-c".to_string(), "basename \"$PWD\"; ulimit -n; test \"$(basename \"$PWD\")\" != b".to_string()];
+        let outcomes = run_each(targets, &command, &ExecOptions::default(), 2);
+
+        let names: Vec<&str> = outcomes.iter().map(|o| o.target.relative.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert!(outcomes[0].succeeded());
+        assert!(!outcomes[1].succeeded());
+        assert!(outcomes[2].succeeded());
+        assert_eq!(outcomes[2].result.as_ref().unwrap().output, b"c\n64\n");
+    }
+}
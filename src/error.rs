@@ -3,8 +3,20 @@ use std::fmt;
 #[derive(Debug)]
 pub enum Error {
     ConfigToolNotFound,
+    ConfigToolIncompatible(String),
+    ConfigToolFailed(String),
     ConfigSaveFailed(String),
     CommandExecutionFailed(String),
+    ResourceLimitExceeded(String),
+    UnsafeCommand(String),
+    SourcesModified { paths: Vec<String>, restored: bool },
+    InvalidStep(String),
+    StepsFailed(Vec<String>),
+    DirectoriesFailed(Vec<String>),
+    InvalidArgument(String),
+    TrashFailed(String),
+    NotClean { dir: String, leftovers: Vec<String> },
+    MakeDryRunFailed(String),
     IoError(std::io::Error),
 }
 
@@ -14,12 +26,61 @@ impl fmt::Display for Error {
             Error::ConfigToolNotFound => {
                 write!(f, "c2rust-config not found. Please install c2rust-config first.")
             }
+            Error::ConfigToolIncompatible(msg) => {
+                write!(f, "Incompatible c2rust-config: {}", msg)
+            }
+            Error::ConfigToolFailed(msg) => {
+                write!(f, "c2rust-config failed: {}", msg)
+            }
             Error::ConfigSaveFailed(msg) => {
                 write!(f, "Failed to save configuration: {}", msg)
             }
             Error::CommandExecutionFailed(msg) => {
                 write!(f, "Command execution failed: {}", msg)
             }
+            Error::ResourceLimitExceeded(msg) => {
+                write!(f, "Resource limit exceeded: {}", msg)
+            }
+            Error::UnsafeCommand(msg) => {
+                write!(f, "Unsafe clean command: {}", msg)
+            }
+            Error::SourcesModified { paths, restored } => {
+                write!(f, "Clean command deleted or modified {} git-tracked file(s):", paths.len())?;
+                for path in paths {
+                    write!(f, "\n  {}", path)?;
+                }
+                if *restored {
+                    write!(f, "\nThe files were restored to their state before the clean.")
+                } else {
+                    write!(f, "\nUse --restore-sources to restore them automatically.")
+                }
+            }
+            Error::InvalidStep(msg) => {
+                write!(f, "Invalid clean step: {}", msg)
+            }
+            Error::StepsFailed(steps) => {
+                write!(f, "{} clean step(s) failed: {}", steps.len(), steps.join(", "))
+            }
+            Error::DirectoriesFailed(dirs) => {
+                write!(f, "Clean failed in {} director{}: {}", dirs.len(),
+                       if dirs.len() == 1 { "y" } else { "ies" }, dirs.join(", "))
+            }
+            Error::InvalidArgument(msg) => {
+                write!(f, "Invalid argument: {}", msg)
+            }
+            Error::TrashFailed(msg) => {
+                write!(f, "Trash operation failed: {}", msg)
+            }
+            Error::NotClean { dir, leftovers } => {
+                write!(f, "Directory '{}' is not clean, {} leftover(s):", dir, leftovers.len())?;
+                for leftover in leftovers {
+                    write!(f, "\n  {}", leftover)?;
+                }
+                Ok(())
+            }
+            Error::MakeDryRunFailed(msg) => {
+                write!(f, "Make dry run failed: {}", msg)
+            }
             Error::IoError(err) => {
                 write!(f, "IO error: {}", err)
             }
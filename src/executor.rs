@@ -1,14 +1,86 @@
 use crate::error::{Error, Result};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use crate::limits::ResourceLimits;
+use crate::runner::{CommandRunner, CommandSpec, Finished, Output, OutputSink, OutputStream, Runner, RunningProcess};
+use crate::sandbox::Sandbox;
+use crate::tracer::{self, DeletionEvent};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Options controlling how the clean command is executed
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    /// Run the command attached to a pseudo-terminal so that tools which
+    /// detect a TTY keep their colors and progress output
+    pub pty: bool,
+    /// Also write everything the command prints to this file
+    pub log_file: Option<PathBuf>,
+    /// Append to `log_file` instead of truncating it
+    pub append_log: bool,
+    /// Remove ANSI escape sequences from the log copy (the live output is untouched)
+    pub strip_ansi: bool,
+    /// Resource limits applied to the child process
+    pub limits: ResourceLimits,
+    /// Run the child in a namespace sandbox where only the clean directory
+    /// (and a private `/tmp`) is writable
+    pub sandbox: bool,
+    /// Trace the command's `unlink`/`rmdir`/`rename` calls and append them to
+    /// this JSON lines file (rejected together with `pty`)
+    pub deletion_ledger: Option<PathBuf>,
+    /// Starts the processes; real ones unless replaced, e.g. by a
+    /// [`RecordingRunner`](crate::runner::RecordingRunner) in tests
+    pub runner: Runner,
+}
+
+/// Wall-clock time and resource usage of a finished command
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+    /// Elapsed wall-clock time in seconds
+    pub wall_secs: f64,
+    /// User CPU time of the child and its waited-for descendants, in seconds
+    pub user_secs: Option<f64>,
+    /// System CPU time of the child and its waited-for descendants, in seconds
+    pub system_secs: Option<f64>,
+    /// Peak resident set size in bytes
+    pub peak_rss_bytes: Option<u64>,
+}
+
+impl std::fmt::Display for RunStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "wall {:.2}s", self.wall_secs)?;
+        if let (Some(user), Some(system)) = (self.user_secs, self.system_secs) {
+            write!(f, ", user {:.2}s, sys {:.2}s", user, system)?;
+        }
+        if let Some(rss) = self.peak_rss_bytes {
+            write!(f, ", peak RSS {:.1} MiB", rss as f64 / (1024.0 * 1024.0))?;
+        }
+        Ok(())
+    }
+}
 
 /// Execute a command in the specified directory with real-time output
-pub fn execute_command(dir: &Path, command: &[String]) -> Result<()> {
+///
+/// Output is always shown live. When `options.log_file` is set, a copy of
+/// the output is also written to that file; in that case stdout/stderr are
+/// captured (or read from the PTY master) instead of simply inherited.
+///
+/// On success, returns the wall time and resource usage of the command.
+pub fn execute_command(dir: &Path, command: &[String], options: &ExecOptions) -> Result<RunStats> {
     if command.is_empty() {
         return Err(Error::CommandExecutionFailed(
             "No command provided".to_string(),
         ));
     }
+    if options.pty && options.deletion_ledger.is_some() {
+        return Err(Error::InvalidArgument(
+            "deletion tracing cannot be combined with a PTY".to_string(),
+        ));
+    }
 
     let program = &command[0];
     let args = &command[1..];
@@ -16,33 +88,51 @@ pub fn execute_command(dir: &Path, command: &[String]) -> Result<()> {
     // Print the command being executed
     println!("Executing command: {} {}", program, args.join(" "));
     println!("In directory: {}", dir.display());
+
+    let log = match &options.log_file {
+        Some(path) => Some(LogSink::create(path, options.append_log, options.strip_ansi)?),
+        None => None,
+    };
+
+    let sandbox = options.sandbox && sandbox_available(dir, &*options.runner);
+    if sandbox {
+        println!("Sandbox: only {} and /tmp are writable", dir.display());
+    }
+    let trace = options.deletion_ledger.is_some();
+    if trace && !tracer::supported() {
+        eprintln!("Warning: Deletion tracing is only supported on Linux (x86_64/aarch64), ignoring it");
+    }
+    let trace = trace && tracer::supported();
     println!();
 
-    // Spawn the command with inherited stdout/stderr for real-time output
-    let mut child = Command::new(program)
-        .args(args)
-        .current_dir(dir)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .map_err(|e| {
-            Error::CommandExecutionFailed(format!(
-                "Failed to execute command '{} {}': {}",
-                program,
-                args.join(" "),
-                e
-            ))
-        })?;
+    let spec = CommandSpec {
+        argv: command.to_vec(),
+        dir: Some(dir.to_path_buf()),
+        inherit_stdin: true,
+        pty: options.pty,
+        limits: options.limits.clone(),
+        sandbox,
+        trace_deletions: trace,
+        process_group: false,
+    };
+    // Capture the output when it is also logged; a PTY has to be read anyway
+    let log = (log.is_some() || options.pty).then(|| Arc::new(Mutex::new(log.unwrap_or_else(LogSink::disabled))));
+    let output = match &log {
+        Some(log) => Output::Stream(tee(Arc::clone(log))),
+        None => Output::Inherit,
+    };
 
-    // Wait for the command to complete
-    let status = child.wait().map_err(|e| {
-        Error::CommandExecutionFailed(format!(
-            "Failed to wait for command '{} {}': {}",
-            program,
-            args.join(" "),
-            e
-        ))
-    })?;
+    let finished = options
+        .runner
+        .spawn(&spec, output)
+        .map_err(|e| run_error("execute", &spec, e))?
+        .wait(None)
+        .map_err(|e| run_error("wait for", &spec, e))?;
+    if let Some(log) = log {
+        log.lock().unwrap().flush().map_err(|e| run_error("wait for", &spec, e))?;
+    }
+    let status = finished.status.ok_or_else(|| run_error("wait for", &spec, std::io::ErrorKind::TimedOut.into()))?;
+    let stats = finished.stats;
 
     // Print exit status
     println!();
@@ -51,8 +141,73 @@ pub fn execute_command(dir: &Path, command: &[String]) -> Result<()> {
     } else {
         println!("Process terminated by signal");
     }
+    println!("Time: {}", stats);
+    if let (true, Some(ledger)) = (trace, &options.deletion_ledger) {
+        if tracer::append_ledger(ledger, &finished.deletions)? {
+            println!("Traced {} deletion(s)", finished.deletions.len());
+        } else {
+            println!("Traced {} deletion(s), not saved since there is no .c2rust directory", finished.deletions.len());
+        }
+    }
     println!();
 
+    check_status(&status, &stats, program, args, &options.limits)?;
+    Ok(stats)
+}
+
+/// "Failed to <action> command '<command>': <error>"
+fn run_error(action: &str, spec: &CommandSpec, e: std::io::Error) -> Error {
+    Error::CommandExecutionFailed(format!("Failed to {} command '{}': {}", action, spec.argv.join(" "), e))
+}
+
+/// Copy output to our own stdout/stderr and to `log`
+fn tee(log: Arc<Mutex<LogSink>>) -> OutputSink {
+    Arc::new(move |stream, data: &[u8]| {
+        match stream {
+            OutputStream::Stdout => {
+                let mut out = std::io::stdout();
+                out.write_all(data)?;
+                out.flush()?;
+            }
+            OutputStream::Stderr => {
+                let mut err = std::io::stderr();
+                err.write_all(data)?;
+                err.flush()?;
+            }
+        }
+        log.lock().unwrap().write(data)
+    })
+}
+
+/// Whether the clean command can run in a sandbox, warning when it cannot
+/// (the command then runs without one)
+fn sandbox_available(dir: &Path, runner: &dyn CommandRunner) -> bool {
+    match runner.probe_sandbox(dir) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!(
+                "Warning: Sandbox unavailable ({}), running the clean command without it. \
+                 Unprivileged user namespaces may be disabled (see kernel.unprivileged_userns_clone \
+                 or user.max_user_namespaces).",
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Turn a non-successful exit status into the matching error
+fn check_status(status: &ExitStatus, stats: &RunStats, program: &str, args: &[String], limits: &ResourceLimits) -> Result<()> {
+    let cpu_used_secs = stats.user_secs.zip(stats.system_secs).map(|(user, system)| user + system);
+    if let Some(reason) = limits.exceeded_by(status, cpu_used_secs) {
+        return Err(Error::ResourceLimitExceeded(format!(
+            "Command '{} {}' {}",
+            program,
+            args.join(" "),
+            reason
+        )));
+    }
+
     if !status.success() {
         return Err(Error::CommandExecutionFailed(format!(
             "Command '{} {}' failed with exit code {}",
@@ -65,20 +220,681 @@ pub fn execute_command(dir: &Path, command: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Exit status and combined output of a command run by [`capture_command`]
+#[derive(Debug)]
+pub struct CapturedRun {
+    pub status: ExitStatus,
+    pub output: Vec<u8>,
+    pub stats: RunStats,
+}
+
+/// Execute a command and collect its combined stdout/stderr instead of streaming it
+///
+/// Both streams share one pipe so the output keeps its original interleaving.
+/// Nothing is printed; the caller decides when and how to show the output.
+/// Only `options.limits` and `options.sandbox` are honoured; PTY and logging
+/// do not apply here.
+pub fn capture_command(dir: &Path, command: &[String], options: &ExecOptions) -> Result<CapturedRun> {
+    if command.is_empty() {
+        return Err(Error::CommandExecutionFailed(
+            "No command provided".to_string(),
+        ));
+    }
+
+    let spec = CommandSpec {
+        argv: command.to_vec(),
+        dir: Some(dir.to_path_buf()),
+        limits: options.limits.clone(),
+        sandbox: options.sandbox && sandbox_available(dir, &*options.runner),
+        ..Default::default()
+    };
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let sink: OutputSink = {
+        let output = Arc::clone(&output);
+        Arc::new(move |_, data: &[u8]| {
+            output.lock().unwrap().extend_from_slice(data);
+            Ok(())
+        })
+    };
+
+    let finished = options
+        .runner
+        .spawn(&spec, Output::Merged(sink))
+        .map_err(|e| run_error("execute", &spec, e))?
+        .wait(None)
+        .map_err(|e| run_error("wait for", &spec, e))?;
+    let status = finished.status.ok_or_else(|| run_error("wait for", &spec, std::io::ErrorKind::TimedOut.into()))?;
+    let output = std::mem::take(&mut *output.lock().unwrap());
+    Ok(CapturedRun { status, output, stats: finished.stats })
+}
+
+/// How long to wait for the output of a killed process whose pipes are still held open
+const DRAIN_GRACE: Duration = Duration::from_millis(200);
+
+/// The [`CommandRunner`] that starts real processes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+    fn starts_real_processes(&self) -> bool {
+        true
+    }
+
+    fn probe_sandbox(&self, dir: &Path) -> std::io::Result<()> {
+        Sandbox::new(dir).and_then(|sandbox| sandbox.probe())
+    }
+
+    fn spawn(&self, spec: &CommandSpec, output: Output) -> std::io::Result<Box<dyn RunningProcess>> {
+        let Some((program, args)) = spec.argv.split_first() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "no command"));
+        };
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        if let Some(dir) = &spec.dir {
+            cmd.current_dir(dir);
+        }
+        if !spec.inherit_stdin {
+            cmd.stdin(Stdio::null());
+        }
+        spec.limits.apply(&mut cmd);
+        if spec.sandbox {
+            let dir = match &spec.dir {
+                Some(dir) => dir.clone(),
+                None => std::env::current_dir()?,
+            };
+            Sandbox::new(&dir)?.apply(&mut cmd);
+        }
+        if spec.trace_deletions {
+            tracer::trace_me(&mut cmd);
+        }
+        #[cfg(unix)]
+        if spec.process_group {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        let started = Instant::now();
+        let (child, pumps) = if spec.pty {
+            let sink = match output {
+                Output::Stream(sink) | Output::Merged(sink) => Some(sink),
+                Output::Inherit => None,
+            };
+            spawn_in_pty(cmd, sink)?
+        } else {
+            match output {
+                Output::Inherit => (cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit()).spawn()?, Vec::new()),
+                Output::Stream(sink) => {
+                    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+                    let pumps = vec![
+                        child.stdout.take().map(|out| pump(out, OutputStream::Stdout, sink.clone())),
+                        child.stderr.take().map(|err| pump(err, OutputStream::Stderr, sink)),
+                    ];
+                    (child, pumps.into_iter().flatten().collect())
+                }
+                Output::Merged(sink) => {
+                    let (reader, writer) = std::io::pipe()?;
+                    cmd.stdout(writer.try_clone()?).stderr(writer);
+                    let child = cmd.spawn()?;
+                    // Close our copies of the write end so the reader sees EOF
+                    drop(cmd);
+                    (child, vec![pump(reader, OutputStream::Stdout, sink)])
+                }
+            }
+        };
+
+        Ok(Box::new(Process {
+            child,
+            pumps,
+            started,
+            trace: spec.trace_deletions,
+            process_group: spec.process_group,
+        }))
+    }
+}
+
+/// A real process started by [`ProcessRunner`]
+struct Process {
+    child: Child,
+    /// Threads forwarding the output to the sink
+    pumps: Vec<JoinHandle<std::io::Result<()>>>,
+    started: Instant,
+    trace: bool,
+    process_group: bool,
+}
+
+impl RunningProcess for Process {
+    fn wait(mut self: Box<Self>, timeout: Option<Duration>) -> std::io::Result<Finished> {
+        // Wait before joining: a traced child makes no progress unless we keep tracing it
+        let mut deletions = Vec::new();
+        let waited = match timeout {
+            _ if self.trace => wait_traced(&mut self.child, timeout.map(|t| Instant::now() + t), &mut deletions),
+            Some(timeout) => self.wait_until(Instant::now() + timeout),
+            None => wait_child(&mut self.child).map(Some),
+        };
+
+        let killed = matches!(waited, Ok(None));
+        for handle in self.pumps {
+            if killed {
+                // Leftover descendants may hold the pipes open; do not wait for them forever
+                let grace = Instant::now() + DRAIN_GRACE;
+                while !handle.is_finished() && Instant::now() < grace {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                if !handle.is_finished() {
+                    continue;
+                }
+            }
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(std::io::Error::other("output thread panicked")))?;
+        }
+
+        let (status, usage) = match waited? {
+            Some((status, usage)) => (Some(status), usage),
+            None => (None, ChildUsage::default()),
+        };
+        Ok(Finished {
+            status,
+            stats: usage.into_stats(self.started.elapsed()),
+            deletions,
+        })
+    }
+}
+
+impl Process {
+    /// Wait for the child, killing it at `deadline`; `None` means it was killed
+    fn wait_until(&mut self, deadline: Instant) -> std::io::Result<Option<(ExitStatus, ChildUsage)>> {
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(Some((status, ChildUsage::default())));
+            }
+            if Instant::now() >= deadline {
+                self.kill();
+                self.child.wait()?;
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn kill(&mut self) {
+        #[cfg(unix)]
+        if self.process_group {
+            // The child leads its own process group, so this also stops anything it started
+            // SAFETY: kill has no memory safety requirements
+            unsafe {
+                libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+            }
+        }
+        let _ = self.child.kill();
+    }
+}
+
+/// CPU time and memory reported by the kernel for a reaped child
+#[derive(Debug, Default)]
+struct ChildUsage {
+    user_secs: Option<f64>,
+    system_secs: Option<f64>,
+    peak_rss_bytes: Option<u64>,
+}
+
+impl ChildUsage {
+    #[cfg(unix)]
+    fn from_rusage(rusage: &libc::rusage) -> Self {
+        let seconds = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+        // ru_maxrss is in kilobytes on Linux and in bytes on macOS
+        let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+        ChildUsage {
+            user_secs: Some(seconds(rusage.ru_utime)),
+            system_secs: Some(seconds(rusage.ru_stime)),
+            peak_rss_bytes: Some(rusage.ru_maxrss as u64 * rss_unit),
+        }
+    }
+
+    fn into_stats(self, wall: std::time::Duration) -> RunStats {
+        RunStats {
+            wall_secs: wall.as_secs_f64(),
+            user_secs: self.user_secs,
+            system_secs: self.system_secs,
+            peak_rss_bytes: self.peak_rss_bytes,
+        }
+    }
+}
+
+/// Wait for `child` to exit, collecting its resource usage via `wait4`
+#[cfg(unix)]
+fn wait_child(child: &mut Child) -> std::io::Result<(ExitStatus, ChildUsage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    let mut raw_status: libc::c_int = 0;
+    // SAFETY: rusage is plain data filled in by the kernel
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: pointers are valid; `pid` is our own unreaped child
+        let rc = unsafe { libc::wait4(pid, &mut raw_status, 0, &mut rusage) };
+        if rc == pid {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    Ok((ExitStatus::from_raw(raw_status), ChildUsage::from_rusage(&rusage)))
+}
+
+/// Trace `child` until it and all its descendants exit, collecting their deletions
+///
+/// Everything traced is killed at `deadline`; `None` means that happened.
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn wait_traced(
+    child: &mut Child,
+    deadline: Option<Instant>,
+    deletions: &mut Vec<DeletionEvent>,
+) -> std::io::Result<Option<(ExitStatus, ChildUsage)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let traced = tracer::trace_child(child, deadline)?;
+    *deletions = traced.events;
+    if traced.timed_out {
+        return Ok(None);
+    }
+    Ok(Some((ExitStatus::from_raw(traced.status), ChildUsage::from_rusage(&traced.rusage))))
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+fn wait_traced(
+    child: &mut Child,
+    _deadline: Option<Instant>,
+    _deletions: &mut Vec<DeletionEvent>,
+) -> std::io::Result<Option<(ExitStatus, ChildUsage)>> {
+    wait_child(child).map(Some)
+}
+
+#[cfg(not(unix))]
+fn wait_child(child: &mut Child) -> std::io::Result<(ExitStatus, ChildUsage)> {
+    Ok((child.wait()?, ChildUsage::default()))
+}
+
+/// Forward everything from `reader` to `sink` on a separate thread until EOF
+fn pump<R: Read + Send + 'static>(mut reader: R, stream: OutputStream, sink: OutputSink) -> JoinHandle<std::io::Result<()>> {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                // On Linux, reading a PTY master after the slave side closed yields EIO
+                Err(e) if e.raw_os_error() == Some(EIO) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            sink(stream, &buf[..n])?;
+        }
+    })
+}
+
+#[cfg(unix)]
+const EIO: i32 = libc::EIO;
+#[cfg(not(unix))]
+const EIO: i32 = 5;
+
+/// Spawn `cmd` with a freshly allocated pseudo-terminal as its stdin/stdout/stderr
+///
+/// The terminal output goes to `sink`, or straight to our stdout without one.
+#[cfg(unix)]
+fn spawn_in_pty(mut cmd: Command, sink: Option<OutputSink>) -> std::io::Result<(Child, Vec<JoinHandle<std::io::Result<()>>>)> {
+    use std::os::unix::process::CommandExt;
+
+    let (master, slave) = open_pty()?;
+
+    let stdio = |f: &File| f.try_clone().map(Stdio::from);
+    cmd.stdin(stdio(&slave)?).stdout(stdio(&slave)?).stderr(stdio(&slave)?);
+    // SAFETY: only async-signal-safe calls are made between fork and exec
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = cmd.spawn()?;
+    // Drop every parent-side handle on the slave so the master sees EOF/EIO
+    // once the child and its descendants exit
+    drop(cmd);
+    drop(slave);
+
+    let sink = sink.unwrap_or_else(|| {
+        Arc::new(|_, data: &[u8]| {
+            let mut out = std::io::stdout();
+            out.write_all(data)?;
+            out.flush()
+        })
+    });
+    Ok((child, vec![pump(master, OutputStream::Stdout, sink)]))
+}
+
+#[cfg(not(unix))]
+fn spawn_in_pty(_cmd: Command, _sink: Option<OutputSink>) -> std::io::Result<(Child, Vec<JoinHandle<std::io::Result<()>>>)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "PTY execution is only supported on Unix platforms",
+    ))
+}
+
+/// Allocate a master/slave PTY pair sized like our own terminal (if any)
+#[cfg(unix)]
+fn open_pty() -> std::io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd;
+
+    let mut master: libc::c_int = -1;
+    let mut slave: libc::c_int = -1;
+
+    // SAFETY: winsize is plain data; ioctl only writes into it on success
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let has_size = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    let size_ptr = if has_size {
+        &mut size as *mut libc::winsize
+    } else {
+        std::ptr::null_mut()
+    };
+
+    // SAFETY: out-pointers are valid for the duration of the call
+    let rc = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            size_ptr as _,
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // Keep the master out of the child (and of any grandchildren)
+    // SAFETY: `master` is a valid descriptor returned by openpty
+    unsafe { libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC) };
+
+    // SAFETY: both descriptors were just created and are owned by us
+    Ok(unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) })
+}
+
+/// Destination for the logged copy of the command output
+struct LogSink {
+    file: Option<File>,
+    stripper: Option<AnsiStripper>,
+}
+
+impl LogSink {
+    fn create(path: &Path, append: bool, strip_ansi: bool) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|e| {
+                Error::CommandExecutionFailed(format!(
+                    "Failed to create log file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Ok(LogSink {
+            file: Some(file),
+            stripper: strip_ansi.then(AnsiStripper::default),
+        })
+    }
+
+    fn disabled() -> Self {
+        LogSink {
+            file: None,
+            stripper: None,
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        match self.stripper.as_mut() {
+            Some(stripper) => file.write_all(&stripper.strip(data)),
+            None => file.write_all(data),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Streaming remover of ANSI escape sequences
+///
+/// State is kept between calls so sequences split across read boundaries
+/// are still removed.
+#[derive(Debug, Default)]
+struct AnsiStripper {
+    state: AnsiState,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum AnsiState {
+    #[default]
+    Text,
+    /// Just saw ESC
+    Escape,
+    /// Inside a CSI sequence (`ESC [` ... final byte)
+    Csi,
+    /// Inside an OSC/DCS-style string (`ESC ]` ... BEL or `ESC \`)
+    String,
+    /// Saw ESC inside a string sequence, expecting `\`
+    StringEscape,
+}
+
+impl AnsiStripper {
+    fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        for &b in data {
+            self.state = match (self.state, b) {
+                (AnsiState::Text, 0x1b) => AnsiState::Escape,
+                (AnsiState::Text, _) => {
+                    out.push(b);
+                    AnsiState::Text
+                }
+                (AnsiState::Escape, b'[') => AnsiState::Csi,
+                (AnsiState::Escape, b']' | b'P' | b'_' | b'^' | b'X') => AnsiState::String,
+                // Two-byte sequences such as `ESC (B` leave one more byte; treat
+                // intermediates as part of the escape
+                (AnsiState::Escape, 0x20..=0x2f) => AnsiState::Escape,
+                (AnsiState::Escape, _) => AnsiState::Text,
+                (AnsiState::Csi, 0x40..=0x7e) => AnsiState::Text,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+                (AnsiState::String, 0x07) => AnsiState::Text,
+                (AnsiState::String, 0x1b) => AnsiState::StringEscape,
+                (AnsiState::String, _) => AnsiState::String,
+                (AnsiState::StringEscape, b'\\') => AnsiState::Text,
+                (AnsiState::StringEscape, _) => AnsiState::String,
+            };
+        }
+        out
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_execute_command_empty() {
-        let result = execute_command(std::path::Path::new("."), &[]);
+        let result = execute_command(std::path::Path::new("."), &[], &ExecOptions::default());
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_execute_command_rejects_traced_pty() {
+        let options = ExecOptions {
+            pty: true,
+            deletion_ledger: Some(PathBuf::from("deletions.jsonl")),
+            ..Default::default()
+        };
+        let result = execute_command(Path::new("."), &["true".to_string()], &options);
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        assert!(!Path::new("deletions.jsonl").exists());
+    }
+
     #[test]
     fn test_execute_command_basic() {
         // Test with a simple command that should succeed
-        let result = execute_command(std::path::Path::new("."), &["echo".to_string(), "test".to_string()]);
+        let result = execute_command(std::path::Path::new("."), &["echo".to_string(), "test".to_string()], &ExecOptions::default());
         assert!(result.is_ok());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_command_combines_streams() {
+        let run = capture_command(
+            Path::new("."),
+            &["sh".to_string(), "-c".to_string(), "echo one; echo two >&2; exit 3".to_string()],
+            &ExecOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(run.status.code(), Some(3));
+        assert!(run.stats.user_secs.is_some());
+        assert_eq!(String::from_utf8_lossy(&run.output), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_sandbox_probe_goes_through_the_runner() {
+        use crate::runner::RecordingRunner;
+
+        // The recorder accepts the sandbox without starting a probe process
+        let recorder = RecordingRunner::new();
+        let options = ExecOptions {
+            sandbox: true,
+            runner: Runner::new(recorder.clone()),
+            ..Default::default()
+        };
+        capture_command(Path::new("/nonexistent"), &["make".to_string(), "clean".to_string()], &options).unwrap();
+        let commands = recorder.commands();
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].sandbox);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_command_reports_usage() {
+        let stats = execute_command(
+            Path::new("."),
+            &["sh".to_string(), "-c".to_string(), "sleep 0.2".to_string()],
+            &ExecOptions::default(),
+        )
+        .unwrap();
+        assert!(stats.wall_secs >= 0.2, "unexpected wall time: {}", stats.wall_secs);
+        assert!(stats.peak_rss_bytes.unwrap_or(0) > 0);
+    }
+
+    #[test]
+    fn test_run_stats_display() {
+        let stats = RunStats {
+            wall_secs: 1.5,
+            user_secs: Some(0.25),
+            system_secs: Some(0.125),
+            peak_rss_bytes: Some(3 * 1024 * 1024),
+        };
+        assert_eq!(stats.to_string(), "wall 1.50s, user 0.25s, sys 0.12s, peak RSS 3.0 MiB");
+    }
+
+    #[test]
+    fn test_strip_ansi_sequences() {
+        let mut stripper = AnsiStripper::default();
+        let out = stripper.strip(b"\x1b[1;31merror\x1b[0m: \x1b]0;title\x07done\x1b(B");
+        assert_eq!(out, b"error: done");
+    }
+
+    #[test]
+    fn test_strip_ansi_split_across_chunks() {
+        let mut stripper = AnsiStripper::default();
+        let mut out = stripper.strip(b"ab\x1b[3");
+        out.extend(stripper.strip(b"2mcd"));
+        assert_eq!(out, b"abcd");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_command_logs_output() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let log = temp_dir.path().join("clean.log");
+        let options = ExecOptions {
+            log_file: Some(log.clone()),
+            ..Default::default()
+        };
+        let result = execute_command(
+            temp_dir.path(),
+            &["sh".to_string(), "-c".to_string(), "echo out; echo err >&2".to_string()],
+            &options,
+        );
+        assert!(result.is_ok());
+        let content = std::fs::read_to_string(&log).unwrap();
+        assert!(content.contains("out"));
+        assert!(content.contains("err"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_command_in_pty() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let log = temp_dir.path().join("clean.log");
+        let options = ExecOptions {
+            pty: true,
+            log_file: Some(log.clone()),
+            strip_ansi: true,
+            ..Default::default()
+        };
+        // `test -t 1` only succeeds when stdout is a terminal
+        let result = execute_command(
+            temp_dir.path(),
+            &[
+                "sh".to_string(),
+                "-c".to_string(),
+                "test -t 1 && printf '\\033[32mgreen\\033[0m\\n'".to_string(),
+            ],
+            &options,
+        );
+        assert!(result.is_ok(), "Expected PTY execution to succeed, got: {:?}", result);
+        let content = std::fs::read_to_string(&log).unwrap();
+        assert!(content.contains("green"));
+        assert!(!content.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_traced_process_times_out() {
+        if !tracer::supported() {
+            return;
+        }
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let spec = CommandSpec {
+            trace_deletions: true,
+            process_group: true,
+            ..CommandSpec::new(["sh", "-c", "sleep 30"]).in_dir(temp_dir.path())
+        };
+        let started = Instant::now();
+        let finished = ProcessRunner
+            .spawn(&spec, Output::Inherit)
+            .unwrap()
+            .wait(Some(Duration::from_millis(200)))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(finished.status.is_none());
+    }
 }
@@ -0,0 +1,180 @@
+use crate::error::Result;
+use crate::project::SKIPPED_DIRS;
+use crate::steps::CleanStep;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Summary of the clean directories' contents after a successful clean
+///
+/// The digest covers the path, type, size and modification time of every
+/// entry, so any file that is added, removed or rewritten changes it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The clean steps (`DIR:COMMAND`) that produced this state
+    pub steps: Vec<String>,
+    /// Scanned directories relative to the project root
+    pub dirs: Vec<String>,
+    pub files: usize,
+    pub digest: String,
+}
+
+impl Fingerprint {
+    /// Fingerprint the directories of `steps`
+    pub fn compute(project_root: &Path, steps: &[CleanStep]) -> Result<Self> {
+        let mut dirs: Vec<String> = Vec::new();
+        for step in steps {
+            if !dirs.contains(&step.dir) {
+                dirs.push(step.dir.clone());
+            }
+        }
+
+        let mut hasher = Fnv64::new();
+        let mut files = 0;
+        for dir in &dirs {
+            let start = match dir.as_str() {
+                "." => PathBuf::new(),
+                dir => PathBuf::from(dir),
+            };
+            hasher.write(dir.as_bytes());
+            walk(project_root, &start, &mut hasher, &mut files)?;
+        }
+
+        Ok(Fingerprint {
+            steps: steps.iter().map(CleanStep::to_spec).collect(),
+            dirs,
+            files,
+            digest: format!("{:016x}", hasher.finish()),
+        })
+    }
+
+    /// Whether the directory contents are the same, regardless of the clean commands
+    pub fn same_contents(&self, other: &Fingerprint) -> bool {
+        self.dirs == other.dirs && self.files == other.files && self.digest == other.digest
+    }
+}
+
+fn walk(project_root: &Path, relative: &Path, hasher: &mut Fnv64, files: &mut usize) -> Result<()> {
+    let dir = project_root.join(relative);
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        let path = relative.join(&name);
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() && SKIPPED_DIRS.iter().any(|d| name == *d) {
+            continue;
+        }
+
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let kind: u8 = if metadata.is_dir() { b'd' } else if metadata.is_symlink() { b'l' } else { b'f' };
+        hasher.write(path.to_string_lossy().as_bytes());
+        hasher.write(&[0, kind]);
+        hasher.write(&metadata.len().to_le_bytes());
+        hasher.write(&mtime.as_secs().to_le_bytes());
+        hasher.write(&mtime.subsec_nanos().to_le_bytes());
+
+        if metadata.is_dir() {
+            walk(project_root, &path, hasher, files)?;
+        } else {
+            *files += 1;
+        }
+    }
+    Ok(())
+}
+
+/// FNV-1a, used because its output is stable across Rust releases
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Self {
+        Fnv64(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Where the fingerprint of `feature` is stored
+pub fn fingerprint_path(project_root: &Path, feature: &str) -> PathBuf {
+    project_root
+        .join(".c2rust")
+        .join("clean")
+        .join("fingerprints")
+        .join(format!("{}.json", feature))
+}
+
+/// Load the fingerprint saved after the last successful clean, if any
+pub fn load(project_root: &Path, feature: &str) -> Option<Fingerprint> {
+    let content = std::fs::read_to_string(fingerprint_path(project_root, feature)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn save(project_root: &Path, feature: &str, fingerprint: &Fingerprint) -> Result<()> {
+    let path = fingerprint_path(project_root, feature);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(fingerprint).map_err(std::io::Error::other)?;
+    std::fs::write(path, json + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn step(dir: &str) -> CleanStep {
+        CleanStep {
+            dir: dir.to_string(),
+            command: vec!["make".to_string(), "clean".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_detects_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("build/sub")).unwrap();
+        fs::write(root.join("build/a.c"), "int a;").unwrap();
+
+        let before = Fingerprint::compute(root, &[step("build")]).unwrap();
+        assert_eq!(before.files, 1);
+        assert!(before.same_contents(&Fingerprint::compute(root, &[step("build")]).unwrap()));
+
+        fs::write(root.join("build/sub/a.o"), "").unwrap();
+        let after = Fingerprint::compute(root, &[step("build")]).unwrap();
+        assert!(!before.same_contents(&after));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_c2rust_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("main.c"), "").unwrap();
+
+        let before = Fingerprint::compute(root, &[step(".")]).unwrap();
+        save(root, "default", &before).unwrap();
+        let after = Fingerprint::compute(root, &[step(".")]).unwrap();
+        assert!(before.same_contents(&after));
+        assert_eq!(load(root, "default"), Some(before));
+    }
+}
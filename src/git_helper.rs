@@ -1,5 +1,6 @@
-use crate::error::Result;
-use std::path::Path;
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 /// Check if there are any modifications in the .c2rust directory and auto-commit if needed.
 /// 
@@ -122,6 +123,243 @@ fn try_auto_commit(c2rust_dir: &Path) -> std::result::Result<(), String> {
     Ok(())
 }
 
+/// Working tree changes to git-tracked files of the project's own repository
+///
+/// Taken before and after the clean command so that only changes made by the
+/// clean itself are reported. Besides the status, the object id of every
+/// changed file's content is kept, so that a file that already had local
+/// modifications is still caught when the clean modifies it again. The
+/// snapshot taken before the clean also copies those files to
+/// `.c2rust/clean/sources/` (when `.c2rust` exists), so they can be restored;
+/// nothing is written to the repository itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackedState {
+    changed: BTreeMap<String, TrackedChange>,
+    /// Where the contents of the changed files were copied, if anywhere
+    backup_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TrackedChange {
+    status: git2::Status,
+    /// Object id of the working tree content; `None` when the file is missing
+    blob: Option<git2::Oid>,
+}
+
+/// Directory holding the copies of locally modified tracked files during a clean
+fn source_backup_dir(project_root: &Path) -> PathBuf {
+    project_root.join(".c2rust").join("clean").join("sources")
+}
+
+/// Status flags that mean a tracked file was deleted or altered in the working tree
+fn tracked_change_flags() -> git2::Status {
+    git2::Status::WT_DELETED | git2::Status::WT_MODIFIED | git2::Status::WT_TYPECHANGE | git2::Status::WT_RENAMED
+}
+
+/// Snapshot the tracked-file changes of the repository at `<project_root>/.git`
+///
+/// Returns `None` when the project root is not a git repository (or it cannot
+/// be read), in which case source protection is skipped.
+pub fn tracked_state(project_root: &Path) -> Option<TrackedState> {
+    if !project_root.join(".git").exists() {
+        return None;
+    }
+
+    match try_tracked_state(project_root, true) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            eprintln!("Warning: Source check skipped: {}", e);
+            None
+        }
+    }
+}
+
+fn try_tracked_state(project_root: &Path, back_up: bool) -> std::result::Result<TrackedState, String> {
+    let repo = git2::Repository::open(project_root)
+        .map_err(|e| format!("Failed to open git repository at {}: {}", project_root.display(), e))?;
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options))
+        .map_err(|e| format!("Failed to read git status: {}", e))?;
+
+    let backup_dir = if back_up && project_root.join(".c2rust").is_dir() {
+        let dir = source_backup_dir(project_root);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        // Keep the copies out of the auto-committed .c2rust repository
+        let gitignore = dir.join(".gitignore");
+        if !gitignore.exists() {
+            std::fs::write(&gitignore, "*\n").map_err(|e| format!("Failed to write {}: {}", gitignore.display(), e))?;
+        }
+        Some(dir)
+    } else {
+        None
+    };
+
+    let workdir = repo.workdir().unwrap_or(project_root).to_path_buf();
+    let mut changed = BTreeMap::new();
+    for entry in statuses.iter().filter(|entry| entry.status().intersects(tracked_change_flags())) {
+        let Some(path) = entry.path() else { continue };
+        let file = workdir.join(path);
+        let blob = if file.is_file() {
+            let oid = git2::Oid::hash_file(git2::ObjectType::Blob, &file)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            if let Some(dir) = &backup_dir {
+                let backup = dir.join(oid.to_string());
+                if !backup.exists() {
+                    std::fs::copy(&file, &backup).map_err(|e| format!("Failed to back up {}: {}", path, e))?;
+                }
+            }
+            Some(oid)
+        } else {
+            None
+        };
+        changed.insert(path.to_string(), TrackedChange { status: entry.status(), blob });
+    }
+
+    Ok(TrackedState { changed, backup_dir })
+}
+
+impl TrackedState {
+    /// Tracked files whose status or content differs from `before`, i.e. that the clean touched
+    ///
+    /// This includes files with local modifications before the clean that it
+    /// reverted to their committed content.
+    pub fn changed_since(&self, before: &TrackedState) -> Vec<String> {
+        let touched = self
+            .changed
+            .iter()
+            .filter(|(path, change)| before.changed.get(*path) != Some(*change))
+            .map(|(path, _)| path.clone());
+        let reverted = before
+            .changed
+            .iter()
+            .filter(|(path, change)| change.blob.is_some() && !self.changed.contains_key(*path))
+            .map(|(path, _)| path.clone());
+        let mut paths: Vec<String> = touched.chain(reverted).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Remove the copies made by this snapshot
+    fn remove_backups(&self) {
+        let Some(dir) = &self.backup_dir else {
+            return;
+        };
+        for blob in self.changed.values().filter_map(|change| change.blob) {
+            let _ = std::fs::remove_file(dir.join(blob.to_string()));
+        }
+    }
+}
+
+/// Directories of the repository at `<project_root>/.git` that hold tracked C
+/// sources or headers, relative to the project root ("" for the root itself)
+///
+/// Empty when the project root is not a git repository.
+pub fn source_dirs(project_root: &Path) -> Vec<PathBuf> {
+    if !project_root.join(".git").exists() {
+        return Vec::new();
+    }
+    let Ok(index) = git2::Repository::open(project_root).and_then(|repo| repo.index()) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<PathBuf> = index
+        .iter()
+        .filter_map(|entry| {
+            let path = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+            let is_source = path.extension().is_some_and(|ext| ext == "c" || ext == "h");
+            is_source.then(|| path.parent().map(Path::to_path_buf).unwrap_or_default())
+        })
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Compare the tracked files after the clean with the snapshot taken before it
+///
+/// Fails with `Error::SourcesModified` listing every tracked file the clean
+/// deleted or modified. With `restore`, those files are first put back the way
+/// they were before the clean: local modifications from the snapshot, everything
+/// else checked out from HEAD.
+pub fn check_sources_unchanged(project_root: &Path, before: &TrackedState, restore: bool) -> Result<()> {
+    if !project_root.join(".git").exists() {
+        return Ok(());
+    }
+    let after = match try_tracked_state(project_root, false) {
+        Ok(after) => after,
+        Err(e) => {
+            eprintln!("Warning: Source check skipped: {}", e);
+            before.remove_backups();
+            return Ok(());
+        }
+    };
+
+    let changed = after.changed_since(before);
+    if changed.is_empty() {
+        before.remove_backups();
+        return Ok(());
+    }
+
+    let restored = if restore {
+        match restore_tracked(project_root, before, &changed) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Warning: Failed to restore tracked files: {}", e);
+                false
+            }
+        }
+    } else {
+        false
+    };
+    before.remove_backups();
+
+    Err(Error::SourcesModified { paths: changed, restored })
+}
+
+/// Put `paths` back to their state in `before`
+fn restore_tracked(project_root: &Path, before: &TrackedState, paths: &[String]) -> std::result::Result<(), String> {
+    let repo = git2::Repository::open(project_root)
+        .map_err(|e| format!("Failed to open git repository at {}: {}", project_root.display(), e))?;
+    let workdir = repo.workdir().unwrap_or(project_root).to_path_buf();
+
+    let mut from_head = Vec::new();
+    for path in paths {
+        match before.changed.get(path).and_then(|change| change.blob) {
+            Some(blob) => {
+                // The copy from the snapshot, or the object when git happens to have it
+                let backup = before.backup_dir.as_ref().map(|dir| dir.join(blob.to_string()));
+                let content = match backup.and_then(|backup| std::fs::read(backup).ok()) {
+                    Some(content) => content,
+                    None => repo
+                        .find_blob(blob)
+                        .map(|blob| blob.content().to_vec())
+                        .map_err(|_| format!("No copy of the local modifications of {} was kept", path))?,
+                };
+                let file = workdir.join(path);
+                if let Some(parent) = file.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| format!("Failed to restore {}: {}", path, e))?;
+                }
+                std::fs::write(&file, content).map_err(|e| format!("Failed to restore {}: {}", path, e))?;
+            }
+            None => from_head.push(path),
+        }
+    }
+    if from_head.is_empty() {
+        return Ok(());
+    }
+
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force();
+    for path in from_head {
+        checkout.path(path);
+    }
+
+    repo.checkout_head(Some(&mut checkout))
+        .map_err(|e| format!("Failed to check out files from HEAD: {}", e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,4 +439,102 @@ mod tests {
         // Note: The warning message would be printed to stderr but we can't easily capture it in unit tests
         // Integration tests can verify the warning output
     }
+
+    /// Create a repository at `dir` with `files` committed on HEAD
+    fn init_repo_with_files(dir: &Path, files: &[(&str, &str)]) -> git2::Repository {
+        let repo = git2::Repository::init(dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test User").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        {
+            let mut index = repo.index().unwrap();
+            index.add_all(["."].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let sig = repo.signature().unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[]).unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn test_tracked_state_without_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(tracked_state(temp_dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_check_sources_detects_deleted_file() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repo_with_files(temp_dir.path(), &[("main.c", "int main;"), ("util.h", "")]);
+
+        let before = tracked_state(temp_dir.path()).unwrap();
+        fs::remove_file(temp_dir.path().join("main.c")).unwrap();
+        fs::write(temp_dir.path().join("main.o"), "").unwrap();
+
+        match check_sources_unchanged(temp_dir.path(), &before, false) {
+            Err(Error::SourcesModified { paths, restored }) => {
+                assert_eq!(paths, vec!["main.c".to_string()]);
+                assert!(!restored);
+            }
+            other => panic!("Expected SourcesModified, got: {:?}", other),
+        }
+        assert!(!temp_dir.path().join("main.c").exists());
+    }
+
+    #[test]
+    fn test_check_sources_restores_and_ignores_prior_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repo_with_files(temp_dir.path(), &[("main.c", "int main;"), ("wip.c", "old")]);
+
+        // A local edit made before the clean must be neither reported nor reverted
+        fs::write(temp_dir.path().join("wip.c"), "work in progress").unwrap();
+        let before = tracked_state(temp_dir.path()).unwrap();
+        fs::remove_file(temp_dir.path().join("main.c")).unwrap();
+
+        match check_sources_unchanged(temp_dir.path(), &before, true) {
+            Err(Error::SourcesModified { paths, restored }) => {
+                assert_eq!(paths, vec!["main.c".to_string()]);
+                assert!(restored);
+            }
+            other => panic!("Expected SourcesModified, got: {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(temp_dir.path().join("main.c")).unwrap(), "int main;");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("wip.c")).unwrap(), "work in progress");
+    }
+
+    #[test]
+    fn test_check_sources_detects_change_to_modified_file() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repo_with_files(temp_dir.path(), &[("wip.c", "old"), ("done.c", "old")]);
+        fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+        let objects = || fs::read_dir(temp_dir.path().join(".git/objects")).unwrap().count();
+        let objects_before = objects();
+
+        // Both files already differ from HEAD; the clean changes one and reverts the other
+        fs::write(temp_dir.path().join("wip.c"), "work in progress").unwrap();
+        fs::write(temp_dir.path().join("done.c"), "finished").unwrap();
+        let before = tracked_state(temp_dir.path()).unwrap();
+        fs::write(temp_dir.path().join("wip.c"), "truncated").unwrap();
+        fs::write(temp_dir.path().join("done.c"), "old").unwrap();
+
+        match check_sources_unchanged(temp_dir.path(), &before, true) {
+            Err(Error::SourcesModified { paths, restored }) => {
+                assert_eq!(paths, vec!["done.c".to_string(), "wip.c".to_string()]);
+                assert!(restored);
+            }
+            other => panic!("Expected SourcesModified, got: {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(temp_dir.path().join("wip.c")).unwrap(), "work in progress");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("done.c")).unwrap(), "finished");
+
+        // The copies lived under .c2rust and are gone again; git's objects are untouched
+        assert_eq!(objects(), objects_before);
+        let backups: Vec<_> = fs::read_dir(source_backup_dir(temp_dir.path())).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(backups, vec![".gitignore"]);
+    }
 }
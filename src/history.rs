@@ -0,0 +1,118 @@
+use crate::error::Result;
+use crate::executor::RunStats;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Metadata recorded for one successful c2rust-clean run
+///
+/// Records are stored as JSON under `.c2rust/clean/runs/<run-id>.json` so
+/// that clean targets whose time or memory usage regresses can be spotted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub run_id: String,
+    /// Start of the run, in seconds since the Unix epoch
+    pub started_at: u64,
+    pub feature: String,
+    pub steps: Vec<StepRecord>,
+}
+
+/// One executed clean command within a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepRecord {
+    /// Directory relative to the project root
+    pub dir: String,
+    pub command: String,
+    pub stats: RunStats,
+}
+
+impl RunRecord {
+    pub fn new(feature: &str) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        RunRecord {
+            run_id: format!("{}-{}", now.as_secs(), std::process::id()),
+            started_at: now.as_secs(),
+            feature: feature.to_string(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn push_step(&mut self, dir: &str, command: &str, stats: RunStats) {
+        self.steps.push(StepRecord {
+            dir: dir.to_string(),
+            command: command.to_string(),
+            stats,
+        });
+    }
+}
+
+/// Directory holding the per-run metadata files
+pub fn runs_dir(project_root: &Path) -> PathBuf {
+    project_root.join(".c2rust").join("clean").join("runs")
+}
+
+/// Write `record` to `.c2rust/clean/runs/<run-id>.json`
+///
+/// Nothing is written when the project has no `.c2rust` directory, since
+/// c2rust-clean does not create that marker on its own. Returns the path of
+/// the written file, if any.
+pub fn save_record(project_root: &Path, record: &RunRecord) -> Result<Option<PathBuf>> {
+    if !project_root.join(".c2rust").is_dir() {
+        return Ok(None);
+    }
+
+    let dir = runs_dir(project_root);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", record.run_id));
+    let json = serde_json::to_string_pretty(record).map_err(std::io::Error::other)?;
+    std::fs::write(&path, json + "\n")?;
+    Ok(Some(path))
+}
+
+/// Features that have at least one saved run record, sorted
+pub fn recorded_features(project_root: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(runs_dir(project_root)) else {
+        return Vec::new();
+    };
+    let mut features: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str::<RunRecord>(&content).ok())
+        .map(|record| record.feature)
+        .collect();
+    features.sort();
+    features.dedup();
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_record_requires_c2rust_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let record = RunRecord::new("default");
+        assert_eq!(save_record(temp_dir.path(), &record).unwrap(), None);
+    }
+
+    #[test]
+    fn test_save_record_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+
+        let mut record = RunRecord::new("default");
+        record.push_step("build", "make clean", RunStats { wall_secs: 0.5, ..Default::default() });
+
+        let path = save_record(temp_dir.path(), &record).unwrap().unwrap();
+        assert!(path.starts_with(runs_dir(temp_dir.path())));
+
+        let loaded: RunRecord = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(loaded, record);
+        assert_eq!(recorded_features(temp_dir.path()), vec!["default".to_string()]);
+    }
+}
@@ -0,0 +1,49 @@
+//! Clean C project build artifacts in the c2rust workflow
+//!
+//! The `c2rust-clean` binary is a thin command line layer over this crate.
+//! [`CleanSession`] runs a clean programmatically: it finds the project root,
+//! runs the clean command or steps, saves the clean configuration and commits
+//! the `.c2rust` directory, without spawning the CLI and parsing its output.
+//!
+//! ```no_run
+//! use c2rust_clean::CleanSession;
+//!
+//! # fn main() -> c2rust_clean::Result<()> {
+//! let session = CleanSession::builder()
+//!     .dir("/path/to/project/build")
+//!     .command(["make", "clean"])
+//!     .build()?;
+//! let report = session.run()?;
+//! for (step, stats) in &report.steps {
+//!     println!("{}: {}", step.to_spec(), stats);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod build_dir;
+pub mod capabilities;
+pub mod compdb;
+pub mod config_helper;
+pub mod config_store;
+pub mod each;
+pub mod error;
+pub mod executor;
+pub mod fingerprint;
+pub mod git_helper;
+pub mod history;
+pub mod limits;
+pub mod makefile;
+pub mod project;
+pub mod runner;
+pub mod safety;
+pub mod sandbox;
+pub mod session;
+pub mod steps;
+pub mod subprocess;
+pub mod tracer;
+pub mod trash;
+pub mod verify;
+
+pub use error::{Error, Result};
+pub use session::{CleanReport, CleanSession, CleanSessionBuilder};
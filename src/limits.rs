@@ -0,0 +1,317 @@
+use crate::error::{Error, Result};
+use std::process::{Command, ExitStatus};
+
+/// Resource limits applied to the clean command before it is executed
+///
+/// Every limit is optional; unset limits are inherited from c2rust-clean.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// CPU time in seconds (RLIMIT_CPU)
+    pub cpu_secs: Option<u64>,
+    /// Address space in bytes (RLIMIT_AS)
+    pub memory_bytes: Option<u64>,
+    /// Maximum number of open file descriptors (RLIMIT_NOFILE)
+    pub open_files: Option<u64>,
+    /// Maximum number of processes for the user (RLIMIT_NPROC)
+    ///
+    /// The kernel counts every process of the user, not just those of the
+    /// clean command, so on a busy machine a small value can make even the
+    /// first fork fail.
+    pub processes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+
+    /// Fill limits that are unset in `self` from `other`
+    pub fn or(self, other: ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            cpu_secs: self.cpu_secs.or(other.cpu_secs),
+            memory_bytes: self.memory_bytes.or(other.memory_bytes),
+            open_files: self.open_files.or(other.open_files),
+            processes: self.processes.or(other.processes),
+        }
+    }
+
+    /// Render the limits as the space separated `key=value` list stored in `clean.limits`
+    pub fn to_spec(&self) -> String {
+        [
+            ("cpu", self.cpu_secs),
+            ("mem", self.memory_bytes),
+            ("nofile", self.open_files),
+            ("nproc", self.processes),
+        ]
+        .iter()
+        .filter_map(|(key, value)| value.map(|v| format!("{}={}", key, v)))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    /// Parse the `key=value` list produced by [`ResourceLimits::to_spec`]
+    pub fn parse_spec(spec: &str) -> Result<ResourceLimits> {
+        let mut limits = ResourceLimits::default();
+        for item in spec.split([' ', ',']).filter(|s| !s.is_empty()) {
+            let (key, value) = item.split_once('=').ok_or_else(|| {
+                Error::InvalidArgument(format!("invalid resource limit '{}', expected KEY=VALUE", item))
+            })?;
+            match key {
+                "cpu" => limits.cpu_secs = Some(parse_count(value)?),
+                "mem" => limits.memory_bytes = Some(parse_size(value)?),
+                "nofile" => limits.open_files = Some(parse_count(value)?),
+                "nproc" => limits.processes = Some(parse_count(value)?),
+                _ => {
+                    return Err(Error::InvalidArgument(format!(
+                        "unknown resource limit '{}' (expected cpu, mem, nofile or nproc)",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Install the limits on `cmd` so they apply to the spawned child only
+    ///
+    /// The limits are soft limits; the current hard limits stay in place so
+    /// that the clean command can still raise them, and a requested value
+    /// above a hard limit is lowered to it. Only the CPU limit also lowers
+    /// the hard limit, to one second past the soft one, so that a child
+    /// ignoring SIGXCPU is still killed.
+    #[cfg(unix)]
+    pub fn apply(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if self.is_empty() {
+            return;
+        }
+
+        let requested: [(LimitResource, &str, Option<u64>); 4] = [
+            (libc::RLIMIT_CPU, "cpu", self.cpu_secs),
+            (libc::RLIMIT_AS, "mem", self.memory_bytes),
+            (libc::RLIMIT_NOFILE, "nofile", self.open_files),
+            (libc::RLIMIT_NPROC, "nproc", self.processes),
+        ];
+        let mut limits = Vec::new();
+        for (resource, name, value) in requested {
+            let Some(value) = value else { continue };
+            // The child inherits our limits, so our hard limit is the ceiling
+            let hard = hard_limit(resource);
+            if value > hard {
+                eprintln!("Warning: resource limit {}={} is above the hard limit {}, using {}", name, value, hard, hard);
+            }
+            let soft = value.min(hard);
+            let hard = if resource == libc::RLIMIT_CPU { soft.saturating_add(1).min(hard) } else { hard };
+            limits.push((resource, soft, hard));
+        }
+
+        // SAFETY: setrlimit is async-signal-safe and only touches the child
+        unsafe {
+            cmd.pre_exec(move || {
+                for (resource, soft, hard) in &limits {
+                    set_limit(*resource, *soft, *hard)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _cmd: &mut Command) {
+        if !self.is_empty() {
+            eprintln!("Warning: resource limits are only supported on Unix platforms, ignoring them");
+        }
+    }
+
+    /// Describe which limit most likely terminated a child with `status`, if any
+    ///
+    /// A SIGKILL is only put down to the CPU limit when the child's measured
+    /// CPU time (`cpu_used_secs`) reached it; a timeout or the OOM killer
+    /// sends the same signal.
+    #[cfg(unix)]
+    pub fn exceeded_by(&self, status: &ExitStatus, cpu_used_secs: Option<f64>) -> Option<String> {
+        use std::os::unix::process::ExitStatusExt;
+
+        let signal = status.signal()?;
+        let cpu_limit_reached = self
+            .cpu_secs
+            .zip(cpu_used_secs)
+            .is_some_and(|(limit, used)| used >= limit as f64);
+        match signal {
+            libc::SIGXCPU => Some(format!(
+                "CPU time limit of {}s exceeded",
+                self.cpu_secs.unwrap_or_default()
+            )),
+            libc::SIGKILL if cpu_limit_reached => Some(format!(
+                "killed after exceeding the CPU time limit of {}s",
+                self.cpu_secs.unwrap_or_default()
+            )),
+            libc::SIGSEGV | libc::SIGABRT | libc::SIGBUS if self.memory_bytes.is_some() => Some(format!(
+                "terminated by signal {} (memory limit of {} bytes probably exceeded)",
+                signal,
+                self.memory_bytes.unwrap_or_default()
+            )),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn exceeded_by(&self, _status: &ExitStatus, _cpu_used_secs: Option<f64>) -> Option<String> {
+        None
+    }
+}
+
+/// The current hard limit of `resource`; unlimited when it cannot be read
+#[cfg(unix)]
+fn hard_limit(resource: LimitResource) -> u64 {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid, writable rlimit for the duration of the call
+    if unsafe { libc::getrlimit(resource, &mut limit) } == -1 {
+        return u64::MAX;
+    }
+    // rlim_t is not 64 bits wide on every platform
+    #[allow(clippy::unnecessary_cast)]
+    let hard = limit.rlim_max as u64;
+    hard
+}
+
+#[cfg(unix)]
+fn set_limit(resource: LimitResource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    // SAFETY: `limit` is a valid rlimit for the duration of the call
+    if unsafe { libc::setrlimit(resource, &limit) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, target_os = "linux", target_env = "gnu"))]
+type LimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type LimitResource = libc::c_int;
+
+fn parse_count(value: &str) -> Result<u64> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidArgument(format!("invalid number '{}'", value)))
+}
+
+/// Parse a byte size with an optional K/M/G/T suffix (powers of 1024)
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let multiplier: u64 = match c.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => return Err(Error::InvalidArgument(format!("invalid size '{}'", value))),
+            };
+            (&value[..i], multiplier)
+        }
+        _ => (value, 1),
+    };
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| Error::InvalidArgument(format!("invalid size '{}'", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("512M").unwrap(), 512 << 20);
+        assert_eq!(parse_size("2g").unwrap(), 2 << 30);
+        assert!(parse_size("12Q").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_spec_round_trip() {
+        let limits = ResourceLimits {
+            cpu_secs: Some(60),
+            memory_bytes: Some(1 << 30),
+            open_files: None,
+            processes: Some(256),
+        };
+        assert_eq!(limits.to_spec(), "cpu=60 mem=1073741824 nproc=256");
+        assert_eq!(ResourceLimits::parse_spec(&limits.to_spec()).unwrap(), limits);
+        assert!(ResourceLimits::parse_spec("disk=1").is_err());
+    }
+
+    #[test]
+    fn test_or_prefers_self() {
+        let cli = ResourceLimits { cpu_secs: Some(10), ..Default::default() };
+        let saved = ResourceLimits { cpu_secs: Some(60), open_files: Some(64), ..Default::default() };
+        let merged = cli.or(saved);
+        assert_eq!(merged.cpu_secs, Some(10));
+        assert_eq!(merged.open_files, Some(64));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_limits_to_child() {
+        let limits = ResourceLimits { open_files: Some(32), ..Default::default() };
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "ulimit -n"]);
+        limits.apply(&mut cmd);
+        let output = cmd.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "32");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cpu_limit_detected() {
+        let limits = ResourceLimits { cpu_secs: Some(1), ..Default::default() };
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "while :; do :; done"]);
+        limits.apply(&mut cmd);
+        let status = cmd.status().unwrap();
+        assert!(limits.exceeded_by(&status, Some(1.0)).is_some(), "expected CPU limit to be reported, got {:?}", status);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_keeps_hard_limit() {
+        let limits = ResourceLimits { open_files: Some(32), ..Default::default() };
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "ulimit -Hn"]);
+        limits.apply(&mut cmd);
+        let output = cmd.output().unwrap();
+        let expected = match hard_limit(libc::RLIMIT_NOFILE) {
+            u64::MAX => "unlimited".to_string(),
+            hard => hard.to_string(),
+        };
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), expected);
+
+        // Asking for more than the hard limit still starts the child
+        let limits = ResourceLimits { open_files: Some(u64::MAX - 1), ..Default::default() };
+        let mut cmd = Command::new("true");
+        limits.apply(&mut cmd);
+        assert!(cmd.status().unwrap().success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sigkill_blamed_on_cpu_limit_only_when_reached() {
+        let limits = ResourceLimits { cpu_secs: Some(10), ..Default::default() };
+        let status = Command::new("sh").args(["-c", "kill -9 $$"]).status().unwrap();
+        assert_eq!(limits.exceeded_by(&status, Some(0.01)), None);
+        assert!(limits.exceeded_by(&status, Some(10.5)).is_some());
+        assert_eq!(limits.exceeded_by(&status, None), None);
+    }
+}
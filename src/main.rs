@@ -1,10 +1,11 @@
-mod config_helper;
-mod error;
-mod executor;
-mod git_helper;
-
-use clap::{Args, Parser, Subcommand};
-use error::Result;
+use c2rust_clean::error::{self, Result};
+use c2rust_clean::project::{find_project_root, locate_project_root, out_of_tree_build_dirs};
+use c2rust_clean::{CleanSession, CleanSessionBuilder};
+use c2rust_clean::{
+    build_dir, compdb, config_helper, config_store, each, executor, fingerprint, git_helper, history, limits,
+    makefile, safety, steps, trash, verify,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
@@ -18,104 +19,1111 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Execute clean command
-    Clean(CommandArgs),
+    Clean(Box<CommandArgs>),
+    /// Restore files moved to the trash by `clean --trash`
+    Restore(RestoreArgs),
+    /// Check that the saved clean directory contains no leftover build artifacts
+    Verify(VerifyArgs),
+    /// Inspect the saved clean configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// List, copy and delete the clean configuration of features
+    Feature {
+        #[command(subcommand)]
+        command: FeatureCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum FeatureCommand {
+    /// List the features that have a saved clean configuration
+    List {
+        #[command(flatten)]
+        config: ConfigArgs,
+    },
+    /// Copy the clean configuration of one feature to another
+    Copy {
+        /// Feature to copy from
+        #[arg(value_parser = parse_feature_name, add = feature_completer())]
+        src: String,
+        /// Feature to copy to
+        #[arg(value_parser = parse_feature_name, add = feature_completer())]
+        dst: String,
+        /// Replace the destination's existing clean configuration
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        config: ConfigArgs,
+    },
+    /// Delete the clean configuration of a feature
+    Delete {
+        #[arg(value_parser = parse_feature_name, add = feature_completer())]
+        name: String,
+        #[command(flatten)]
+        config: ConfigArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Show the saved clean settings of one or all features
+    Show(ConfigShowArgs),
 }
 
 #[derive(Args)]
-struct CommandArgs {
+struct ConfigShowArgs {
+    /// Feature to show (default: "default")
+    #[arg(long, conflicts_with = "all_features", value_parser = parse_feature_name, add = feature_completer())]
+    feature: Option<String>,
+
+    /// Show every feature with saved configuration or recorded runs
+    #[arg(long)]
+    all_features: bool,
+
+    /// Print the settings as JSON
+    #[arg(long)]
+    json: bool,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+/// Options selecting where the clean configuration is stored
+#[derive(Args)]
+struct ConfigArgs {
+    /// Configuration backend: c2rust-config, the native `.c2rust/clean/config.toml`
+    /// file, or auto (c2rust-config when installed, native otherwise)
+    #[arg(long, value_enum, default_value_t = config_store::ConfigBackend::Auto)]
+    config_backend: config_store::ConfigBackend,
+
+    /// Path of the c2rust-config binary (C2RUST_CONFIG takes precedence)
+    #[arg(long, value_name = "PATH")]
+    config_tool: Option<PathBuf>,
+
+    /// Print where c2rust-config was looked for and which one is used
+    #[arg(long)]
+    explain: bool,
+
+    /// Seconds a single c2rust-config call may take before it is killed
+    #[arg(long, value_name = "SECS", default_value_t = config_helper::DEFAULT_CONFIG_TIMEOUT.as_secs(),
+          value_parser = clap::value_parser!(u64).range(1..))]
+    config_timeout: u64,
+}
+
+impl ConfigArgs {
+    /// Apply these options to a clean session
+    fn configure(&self, builder: CleanSessionBuilder) -> CleanSessionBuilder {
+        let builder = builder
+            .config_backend(self.config_backend)
+            .config_timeout(std::time::Duration::from_secs(self.config_timeout))
+            .explain_config_tool(self.explain);
+        match &self.config_tool {
+            Some(path) => builder.config_tool(path),
+            None => builder,
+        }
+    }
+
+    fn open(&self, project_root: &Path) -> Result<Box<dyn config_store::ConfigStore>> {
+        if self.explain {
+            let resolution = config_helper::resolve_c2rust_config(self.config_tool.as_deref(), Some(project_root));
+            eprintln!("{}", resolution.explain());
+        }
+        let tool = config_helper::ConfigToolOptions {
+            path: self.config_tool.clone(),
+            timeout: std::time::Duration::from_secs(self.config_timeout),
+            ..Default::default()
+        };
+        config_store::open(self.config_backend, &tool, project_root)
+    }
+}
+
+#[derive(Args)]
+struct VerifyArgs {
     /// Optional feature name (default: "default")
+    #[arg(long, value_parser = parse_feature_name, add = feature_completer())]
+    feature: Option<String>,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Artifact name pattern to look for, e.g. '*.o' (repeatable; defaults to
+    /// the `clean.artifacts` configuration or built-in C patterns)
+    #[arg(long = "artifact", value_name = "GLOB")]
+    artifacts: Vec<String>,
+}
+
+#[derive(Args)]
+struct RestoreArgs {
+    /// Run id to restore; lists the trashed runs when omitted
+    run_id: Option<String>,
+
+    /// Overwrite files that exist again at their original location
     #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct CommandArgs {
+    /// Optional feature name (default: "default")
+    #[arg(long, value_parser = parse_feature_name, add = feature_completer())]
     feature: Option<String>,
 
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Run the clean command under a pseudo-terminal (keeps colors and progress output)
+    #[arg(long)]
+    pty: bool,
+
+    /// Also save the clean command output to this file
+    #[arg(long, value_name = "FILE")]
+    log: Option<PathBuf>,
+
+    /// Strip ANSI escape sequences from the saved log (requires --log)
+    #[arg(long, requires = "log")]
+    strip_ansi: bool,
+
+    /// Add a clean step as "[DIR:]COMMAND" (repeatable, run in order).
+    /// DIR is relative to the project root and defaults to the current directory
+    #[arg(long = "step", value_name = "STEP", conflicts_with = "clean_cmd")]
+    steps: Vec<String>,
+
+    /// Read clean steps from a file, one "[DIR:]COMMAND" per line
+    #[arg(long, value_name = "FILE", conflicts_with = "clean_cmd")]
+    steps_file: Option<PathBuf>,
+
+    /// Keep running the remaining steps after a step fails
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Run the clean command in every directory matching this glob (relative to the current directory)
+    #[arg(long, value_name = "GLOB", conflicts_with_all = ["steps", "steps_file", "pty", "log"])]
+    each: Option<String>,
+
+    /// Maximum number of directories cleaned in parallel with --each (default: number of CPUs)
+    #[arg(short = 'j', long, value_name = "N", requires = "each", value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+
+    /// Limit the CPU time of the clean command, in seconds (RLIMIT_CPU)
+    #[arg(long, value_name = "SECS")]
+    limit_cpu: Option<u64>,
+
+    /// Limit the address space of the clean command, e.g. 2G or 512M (RLIMIT_AS)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    limit_mem: Option<u64>,
+
+    /// Limit the number of open files of the clean command (RLIMIT_NOFILE)
+    #[arg(long, value_name = "N")]
+    limit_nofile: Option<u64>,
+
+    /// Limit the number of processes of the user for the clean command
+    /// (RLIMIT_NPROC; counts all of the user's processes, not only the clean's)
+    #[arg(long, value_name = "N")]
+    limit_nproc: Option<u64>,
+
+    /// Additional path (relative to the project root) that clean commands must not remove (repeatable)
+    #[arg(long = "protect", value_name = "PATH")]
+    protected: Vec<PathBuf>,
+
+    /// Overwrite a saved clean configuration that differs from this run
+    #[arg(long, conflicts_with = "no_save")]
+    update_config: bool,
+
+    /// Run the clean without saving anything to the configuration
+    #[arg(long)]
+    no_save: bool,
+
+    /// Source directory of an out-of-tree build run from the current directory
+    /// (default: read from CMakeCache.txt or meson-info)
+    #[arg(long, value_name = "DIR")]
+    source_dir: Option<PathBuf>,
+
+    /// Do not run `make -n` to preview and check the commands of a make-based clean
+    #[arg(long)]
+    no_make_dry_run: bool,
+
+    /// Skip the safety check that rejects destructive commands escaping the project root
+    #[arg(long = "i-know-what-im-doing")]
+    skip_safety_check: bool,
+
+    /// Run the clean command in a Linux namespace sandbox where only the clean
+    /// directory and a private /tmp are writable
+    #[arg(long, conflicts_with = "from_compdb")]
+    sandbox: bool,
+
+    /// Skip the clean when the clean directories are unchanged since the last
+    /// successful `--if-needed` clean (the configuration is still updated)
+    #[arg(long, conflicts_with_all = ["each", "from_compdb"])]
+    if_needed: bool,
+
+    /// After a successful clean, check that the clean directories contain no
+    /// leftover artifacts or untracked files (see the `verify` subcommand)
+    #[arg(long, conflicts_with = "each")]
+    verify: bool,
+
+    /// Compilation database whose outputs (`-o` targets) are checked after the
+    /// clean (default: compile_commands.json in the current directory)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = compdb::COMPDB_FILE, conflicts_with = "each")]
+    compdb: Option<PathBuf>,
+
+    /// Delete the outputs listed in the compilation database directly instead
+    /// of running a clean command
+    #[arg(long, conflicts_with_all = ["clean_cmd", "steps", "steps_file", "each"])]
+    from_compdb: bool,
+
+    /// Trace the files the clean command deletes or renames (via ptrace, Linux only)
+    /// and save the ledger to .c2rust/clean/runs/<run-id>.deletions.jsonl
+    #[arg(long, conflicts_with_all = ["each", "pty", "from_compdb"])]
+    trace_deletions: bool,
+
+    /// Do not check whether the clean deleted or modified git-tracked files
+    #[arg(long, conflicts_with = "restore_sources")]
+    no_source_check: bool,
+
+    /// Restore git-tracked files deleted or modified by the clean from HEAD
+    #[arg(long)]
+    restore_sources: bool,
+
+    /// Move files removed by the clean to .c2rust/trash/<run-id>/ so they can be restored
+    #[arg(long, conflicts_with = "each")]
+    trash: bool,
+
+    /// Remove trashed runs older than this many days
+    #[arg(long, value_name = "DAYS", default_value_t = 7, requires = "trash")]
+    trash_max_age: u64,
+
+    /// Remove the oldest trashed runs while the trash is larger than this, e.g. 1G
+    #[arg(long, value_name = "SIZE", default_value = "1G", value_parser = parse_size_arg, requires = "trash")]
+    trash_max_size: u64,
+
     /// Clean command to execute - use after '--' separator
     /// Example: c2rust-clean clean -- make clean
-    #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true, value_name = "CLEAN_CMD")]
+    #[arg(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        required_unless_present_any = ["steps", "steps_file", "from_compdb"],
+        value_name = "CLEAN_CMD"
+    )]
     clean_cmd: Vec<String>,
 }
 
-/// Find the project root directory by searching for marker files/directories.
-/// Searches upward from start_dir for directories containing:
-/// - .git directory (Git repository root)
-/// - Cargo.toml (Rust project root)
-/// - .c2rust directory (c2rust project marker)
-/// If none found, returns the start_dir as root.
-fn find_project_root(start_dir: &Path) -> Result<PathBuf> {
-    let mut current = start_dir;
-    
-    // List of marker files/directories that indicate a project root
-    let markers = [".git", "Cargo.toml", ".c2rust"];
-    
-    loop {
-        // Check if any marker exists in the current directory
-        for marker in &markers {
-            let marker_path = current.join(marker);
-            if marker_path.exists() {
-                return Ok(current.to_path_buf());
-            }
+impl CommandArgs {
+    /// A clean session of `dir` configured by these arguments
+    fn session_builder(&self, dir: &Path, feature: &str) -> CleanSessionBuilder {
+        let mut builder = self
+            .config
+            .configure(CleanSession::builder())
+            .dir(dir)
+            .feature(feature)
+            .command(self.clean_cmd.iter().cloned())
+            .keep_going(self.keep_going)
+            .trace_deletions(self.trace_deletions)
+            .skip_safety_check(self.skip_safety_check)
+            .exec_options(executor::ExecOptions {
+                pty: self.pty,
+                log_file: self.log.clone(),
+                strip_ansi: self.strip_ansi,
+                limits: self.cli_limits(),
+                sandbox: self.sandbox,
+                ..Default::default()
+            });
+        if let Some(source_dir) = &self.source_dir {
+            builder = builder.source_dir(source_dir);
+        }
+        if let Some(path) = &self.steps_file {
+            builder = builder.steps_file(path);
         }
-        
-        // Move to parent directory
-        match current.parent() {
-            Some(parent) => current = parent,
-            None => return Ok(start_dir.to_path_buf()),
+        for spec in &self.steps {
+            builder = builder.step(spec);
+        }
+        for path in &self.protected {
+            builder = builder.protect(path);
+        }
+        builder
+    }
+
+    /// Resource limits given on the command line
+    fn cli_limits(&self) -> limits::ResourceLimits {
+        limits::ResourceLimits {
+            cpu_secs: self.limit_cpu,
+            memory_bytes: self.limit_mem,
+            open_files: self.limit_nofile,
+            processes: self.limit_nproc,
+        }
+    }
+
+    /// Build the safety policy for `project_root` and the out-of-tree `build_dirs`,
+    /// or `None` when it was overridden
+    fn safety_policy(&self, project_root: &Path, build_dirs: &[PathBuf]) -> Option<safety::SafetyPolicy> {
+        if self.skip_safety_check {
+            eprintln!("Warning: safety check disabled by --i-know-what-im-doing");
+            return None;
+        }
+        Some(self.flagging_policy(project_root, build_dirs))
+    }
+
+    /// The safety policy regardless of `--i-know-what-im-doing`, for flagging only
+    fn flagging_policy(&self, project_root: &Path, build_dirs: &[PathBuf]) -> safety::SafetyPolicy {
+        build_dirs.iter().fold(
+            safety::SafetyPolicy::new(project_root, &self.protected),
+            |policy, dir| policy.allow_dir(dir),
+        )
+    }
+
+    /// Snapshot the tracked files of the project's repository, unless disabled
+    fn source_snapshot(&self, project_root: &Path) -> Option<git_helper::TrackedState> {
+        if self.no_source_check {
+            return None;
         }
+        git_helper::tracked_state(project_root)
+    }
+}
+
+fn parse_size_arg(value: &str) -> std::result::Result<u64, String> {
+    limits::parse_size(value).map_err(|e| e.to_string())
+}
+
+fn parse_feature_name(value: &str) -> std::result::Result<String, String> {
+    config_helper::validate_feature_name(value)
+        .map(|()| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// How long completion waits for a single c2rust-config call
+const COMPLETION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Shell completion of the features known in the current project
+///
+/// Runs on every tab press, so it only asks the store for its feature list
+/// (at most a probe and one c2rust-config call, each cut short quickly).
+fn feature_completer() -> clap_complete::ArgValueCandidates {
+    clap_complete::ArgValueCandidates::new(|| {
+        let Ok(project_root) = std::env::current_dir().map(|dir| find_project_root(&dir).unwrap_or(dir)) else {
+            return Vec::new();
+        };
+        // Completion must not print, so pick the store without the fallback note
+        let subprocess = config_helper::SubprocessStore::new(&project_root).with_timeout(COMPLETION_TIMEOUT);
+        let store: Box<dyn config_store::ConfigStore> =
+            match config_helper::check_config_tool(subprocess.program(), COMPLETION_TIMEOUT, &executor::ProcessRunner) {
+                Ok(()) => Box::new(subprocess),
+                Err(_) => Box::new(config_store::NativeStore::new(&project_root)),
+            };
+        store
+            .features()
+            .unwrap_or_default()
+            .into_iter()
+            .map(clap_complete::CompletionCandidate::new)
+            .collect()
+    })
+}
+
+/// Features that may have a saved clean configuration: those the store
+/// reports, those with recorded runs, and "default"
+fn candidate_features(store: &dyn config_store::ConfigStore, project_root: &Path) -> Result<Vec<String>> {
+    let mut features = store.features()?;
+    features.extend(history::recorded_features(project_root));
+    features.push("default".to_string());
+    features.sort();
+    features.dedup();
+    Ok(features)
+}
+
+/// Combine the limits given on the command line with those saved in `clean.limits`.
+/// Command line values take precedence.
+fn resolve_limits(args: &CommandArgs, feature: &str, store: &dyn config_store::ConfigStore) -> Result<limits::ResourceLimits> {
+    let saved = config_helper::load_limits(store, Some(feature))?;
+    Ok(args.cli_limits().or(saved))
+}
+
+/// Save the command line limits (if any were given) next to the clean command
+fn save_cli_limits(args: &CommandArgs, feature: &str, store: &dyn config_store::ConfigStore) -> Result<()> {
+    let cli_limits = args.cli_limits();
+    if cli_limits.is_empty() {
+        return Ok(());
     }
+    let saved = config_helper::load_limits(store, Some(feature))?;
+    config_helper::save_limits(store, &cli_limits.or(saved), Some(feature))
 }
 
 fn run(args: CommandArgs) -> Result<()> {
-    // 1. Check if c2rust-config exists
-    config_helper::check_c2rust_config_exists()?;
+    if args.from_compdb {
+        return run_from_compdb(&args);
+    }
 
-    // 2. Get feature name (default to "default")
+    // 1. Get feature name (default to "default")
     let feature = args.feature.as_deref().unwrap_or("default");
 
-    // 3. Get the current working directory (where the command is executed)
+    // 2. Get the current working directory (where the command is executed)
     let current_dir = std::env::current_dir()?;
+
+    if let Some(pattern) = &args.each {
+        return run_each(pattern, &args, feature, &current_dir);
+    }
     
-    // 4. Find the project root by searching for marker files
-    // Start from current directory and search upward for .git, Cargo.toml, or .c2rust
-    let project_root = find_project_root(&current_dir)?;
-    
-    // 5. Calculate the clean directory relative to project root
-    let clean_dir_relative = current_dir.strip_prefix(&project_root)
-        .map(|p| {
-            if p.as_os_str().is_empty() {
-                ".".to_string()
-            } else {
-                p.display().to_string()
-            }
-        })
-        .unwrap_or_else(|_| {
-            eprintln!("Warning: current directory is not under project root, using '.' as clean directory");
-            ".".to_string()
-        });
+    // 3. Find the project root by searching for marker files
+    // Start from current directory (or the source directory of an out-of-tree
+    // build) and search upward for .git, Cargo.toml, or .c2rust, open the
+    // configuration store and resolve the clean steps
+    let session = args.session_builder(&current_dir, feature).build()?;
+    let project_root = session.project_root();
+    let store = session.store();
+    let clean_steps = session.steps();
+    let exec_options = session.exec_options();
 
     // Print the calculated paths to stderr for debugging
     eprintln!("Project root: {}", project_root.display());
     eprintln!("Current directory: {}", current_dir.display());
-    eprintln!("Relative clean directory: {}", clean_dir_relative);
+    eprintln!("Relative clean directory: {}", session.clean_dir_relative());
     eprintln!();
 
-    // Execute the clean command in the current directory
-    executor::execute_command(&current_dir, &args.clean_cmd)?;
+    // Refuse destructive commands that escape the project root.
+    // Every step is checked up front so nothing runs if any step is unsafe
+    if let Some(warning) = session.check_safety()? {
+        eprintln!("Warning: {}", warning);
+    }
+
+    // Compare with the saved configuration before anything runs, so a
+    // confirmation prompt does not wait until after the clean
+    let save_config = confirm_config_update(&args, store, &session.config_entries()?, feature)?;
+
+    // Skip the clean when nothing changed since the last successful one
+    if args.if_needed {
+        let current = fingerprint::Fingerprint::compute(project_root, clean_steps)?;
+        let saved = fingerprint::load(project_root, feature);
+        if let Some(saved) = saved.filter(|saved| saved.same_contents(&current)) {
+            println!("✓ Clean directory unchanged since the last successful clean, skipping the clean command.");
+            if saved.steps != current.steps && save_config {
+                session.save_config()?;
+                fingerprint::save(project_root, feature, &current)?;
+                session.commit()?;
+                println!("✓ Configuration saved.");
+            }
+            return Ok(());
+        }
+    }
+
+    // Preview make-based steps with `make -n` before anything runs
+    if !args.no_make_dry_run {
+        for step in clean_steps.iter().filter(|step| makefile::is_make(&step.command)) {
+            preview_make_step(&args, &session, step)?;
+        }
+    }
+
+    // Remember which tracked files were already changed before the clean
+    let sources_before = args.source_snapshot(project_root);
+
+    // Snapshot the clean directories so removed files can be moved to the trash
+    let trash_snapshot = if args.trash {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for step in clean_steps {
+            let dir = safety::normalize(&project_root.join(&step.dir));
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        Some(trash::TrashSnapshot::take(project_root, session.run_id(), &dirs)?)
+    } else {
+        None
+    };
+
+    // Read the compilation database up front: the clean may well delete it
+    let compdb_outputs = match &args.compdb {
+        Some(path) => Some((path, compdb::load_outputs(path)?)),
+        None => None,
+    };
 
-    // Save configuration using c2rust-config
-    let command_str = args.clean_cmd.join(" ");
-    config_helper::save_config(&clean_dir_relative, &command_str, Some(feature), &project_root)?;
+    // Execute the clean command(s)
+    let result = session.execute();
+    if let Some(snapshot) = trash_snapshot {
+        finish_trash(project_root, snapshot, session.run_id(), &args);
+    }
+    let step_stats = check_sources(project_root, sources_before.as_ref(), args.restore_sources, result)?;
+
+    if save_config {
+        session.save_config()?;
+    }
+    if args.if_needed {
+        let fingerprint = fingerprint::Fingerprint::compute(project_root, clean_steps)?;
+        fingerprint::save(project_root, feature, &fingerprint)?;
+    }
+    let record = session.record(step_stats);
+    if !args.no_save {
+        session.save_limits()?;
+    }
+    let record_path = history::save_record(project_root, &record)?;
 
     // Auto-commit changes in .c2rust directory if any
-    git_helper::auto_commit_if_modified(&project_root)?;
+    session.commit()?;
 
     println!("\n✓ Clean command executed successfully.");
-    println!("✓ Configuration saved.");
+    for step in &record.steps {
+        println!("✓ Time ({}: {}): {}", step.dir, step.command, step.stats);
+    }
+    if save_config {
+        println!("✓ Configuration saved.");
+    }
+    if let Some(path) = record_path {
+        println!("✓ Run metadata saved to {}", path.display());
+    }
+    if let Some(path) = exec_options.deletion_ledger.as_ref().filter(|path| path.exists()) {
+        println!("✓ Deletion ledger saved to {}", path.display());
+    }
+
+    if let Some((path, outputs)) = compdb_outputs {
+        report_compdb_outputs(path, &outputs);
+    }
+
+    if args.verify {
+        let patterns = artifact_patterns(&[], feature, store)?;
+        let mut verified: Vec<&str> = Vec::new();
+        for step in &record.steps {
+            if !verified.contains(&step.dir.as_str()) {
+                verify::verify_dir(project_root, &step.dir, &patterns)?;
+                verified.push(&step.dir);
+            }
+        }
+        println!("✓ Verified that {} is clean.", verified.join(", "));
+    }
+    Ok(())
+}
+
+/// Show what `make -n` says a make-based step would run and flag dangerous commands.
+///
+/// Flagged commands are an error unless the safety check is disabled with
+/// `--i-know-what-im-doing`, in which case they are only reported.
+fn preview_make_step(args: &CommandArgs, session: &CleanSession, step: &steps::CleanStep) -> Result<()> {
+    let dir = match step.dir.as_str() {
+        "." => session.project_root().to_path_buf(),
+        dir => session.project_root().join(dir),
+    };
+    if !dir.is_dir() {
+        // run_steps reports the missing directory
+        return Ok(());
+    }
+
+    let mut planned = makefile::dry_run(&dir, &step.command, session.exec_options())?;
+    makefile::flag_dangerous(&mut planned, &session.safety_policy());
+
+    println!("Dry run of '{}' would execute:", steps::join_command(&step.command));
+    for command in &planned {
+        let location = command.dir.strip_prefix(&dir).unwrap_or(&command.dir);
+        let prefix = if location.as_os_str().is_empty() {
+            String::new()
+        } else {
+            format!("[{}] ", location.display())
+        };
+        println!("  {}{}", prefix, command.line);
+        if let Some(warning) = &command.warning {
+            println!("    ⚠ {}", warning);
+        }
+    }
+    if planned.is_empty() {
+        println!("  (nothing)");
+    }
+    println!();
+
+    let flagged: Vec<&makefile::PlannedCommand> = planned.iter().filter(|c| c.warning.is_some()).collect();
+    if flagged.is_empty() || args.skip_safety_check {
+        return Ok(());
+    }
+    Err(error::Error::UnsafeCommand(format!(
+        "'{}' would run {} dangerous command(s): {}. Use --i-know-what-im-doing to override",
+        steps::join_command(&step.command),
+        flagged.len(),
+        flagged.iter().map(|c| c.line.as_str()).collect::<Vec<_>>().join("; ")
+    )))
+}
+
+/// Warn about outputs of the compilation database at `path` that survived the clean
+fn report_compdb_outputs(path: &Path, outputs: &[PathBuf]) {
+    let remaining = compdb::existing(outputs);
+    if remaining.is_empty() {
+        println!("✓ None of the {} output(s) listed in {} remain.", outputs.len(), path.display());
+        return;
+    }
+    eprintln!(
+        "Warning: {} of {} output(s) listed in {} still exist after the clean:",
+        remaining.len(),
+        outputs.len(),
+        path.display()
+    );
+    for output in remaining {
+        eprintln!("  {}", output.display());
+    }
+}
+
+/// Built-in clean: delete the outputs listed in the compilation database.
+///
+/// Every output is checked against the safety policy before anything is
+/// removed. The source check, `--trash`, the run record and `--verify` work
+/// as for a clean command; no configuration is saved since none was run.
+fn run_from_compdb(args: &CommandArgs) -> Result<()> {
+    let feature = args.feature.as_deref().unwrap_or("default");
+    let current_dir = std::env::current_dir()?;
+    let project_root = locate_project_root(&current_dir, args.source_dir.as_deref())?;
+    let explicit: Vec<PathBuf> = args.source_dir.iter().map(|_| current_dir.clone()).collect();
+    let build_dirs = out_of_tree_build_dirs(&project_root, std::slice::from_ref(&current_dir), &explicit);
+    let path = args.compdb.clone().unwrap_or_else(|| PathBuf::from(compdb::COMPDB_FILE));
+    let outputs = compdb::load_outputs(&path)?;
+    let remaining = compdb::existing(&outputs);
+
+    if let Some(policy) = args.safety_policy(&project_root, &build_dirs) {
+        for output in &remaining {
+            let command = ["rm".to_string(), "-f".to_string(), output.display().to_string()];
+            policy.check(&project_root, &command)?;
+        }
+    }
+
+    let mut record = history::RunRecord::new(feature);
+    let sources_before = args.source_snapshot(&project_root);
+    let trash_snapshot = if args.trash {
+        Some(trash::TrashSnapshot::take_files(&project_root, &record.run_id, &remaining)?)
+    } else {
+        None
+    };
+
+    let started = std::time::Instant::now();
+    let result = remaining.iter().try_for_each(|output| {
+        std::fs::remove_file(output)?;
+        println!("Removed {}", output.display());
+        Ok(())
+    });
+    if let Some(snapshot) = trash_snapshot {
+        finish_trash(&project_root, snapshot, &record.run_id, args);
+    }
+    check_sources(&project_root, sources_before.as_ref(), args.restore_sources, result)?;
+
+    let clean_dir = build_dir::relative_dir(&current_dir, &project_root);
+    let stats = executor::RunStats {
+        wall_secs: started.elapsed().as_secs_f64(),
+        ..Default::default()
+    };
+    let command = steps::join_command(&["--from-compdb".to_string(), path.display().to_string()]);
+    record.push_step(&clean_dir, &command, stats);
+    let record_path = history::save_record(&project_root, &record)?;
+    git_helper::auto_commit_if_modified(&project_root)?;
+
+    println!(
+        "\n✓ Removed {} of {} output(s) listed in {}.",
+        remaining.len(),
+        outputs.len(),
+        path.display()
+    );
+    if let Some(path) = record_path {
+        println!("✓ Run metadata saved to {}", path.display());
+    }
+
+    if args.verify {
+        let store = args.config.open(&project_root)?;
+        let patterns = artifact_patterns(&[], feature, store.as_ref())?;
+        verify::verify_dir(&project_root, &clean_dir, &patterns)?;
+        println!("✓ Verified that {} is clean.", clean_dir);
+    }
+    Ok(())
+}
+
+/// Decide whether `entries` may be saved for `feature`
+///
+/// Saving is always allowed when it would not change any saved value.
+/// Otherwise the differences are shown and the saved configuration is only
+/// overwritten with `--update-config` or after confirmation on a terminal.
+fn confirm_config_update(
+    args: &CommandArgs,
+    store: &dyn config_store::ConfigStore,
+    entries: &[config_store::ConfigEntry],
+    feature: &str,
+) -> Result<bool> {
+    if args.no_save {
+        return Ok(false);
+    }
+    let changes = config_helper::config_changes(store, entries, Some(feature))?;
+    if changes.is_empty() {
+        return Ok(true);
+    }
+
+    eprintln!("The saved clean configuration of feature '{}' differs from this run:", feature);
+    for change in &changes {
+        eprintln!("{}", change);
+    }
+    if args.update_config {
+        eprintln!("Updating the saved configuration (--update-config).");
+        return Ok(true);
+    }
+
+    use std::io::IsTerminal;
+    if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        eprint!("Overwrite the saved configuration? [y/N] ");
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            return Ok(true);
+        }
+    }
+    eprintln!("Warning: keeping the saved configuration; pass --update-config to overwrite it or --no-save to silence this check");
+    Ok(false)
+}
+
+/// Run the clean command in every directory matching `pattern`, in parallel.
+///
+/// Each directory gets its own project root and relative `clean.dir`. When
+/// all directories pass, the configuration is saved per project root: a
+/// single directory is saved as `clean.dir`/`clean.cmd`, several directories
+/// sharing a root are saved as an ordered `clean.steps` list.
+fn run_each(pattern: &str, args: &CommandArgs, feature: &str, current_dir: &Path) -> Result<()> {
+    let jobs = args.jobs.map(|j| j as usize).unwrap_or_else(|| {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    });
+
+    // Each target uses the limits saved in its own project root
+    let mut root_limits: Vec<(PathBuf, limits::ResourceLimits)> = Vec::new();
+    let mut targets = Vec::new();
+    for dir in each::expand_dirs(pattern, current_dir)? {
+        let project_root = locate_project_root(&dir, args.source_dir.as_deref())?;
+        let relative = build_dir::relative_dir(&dir, &project_root);
+        let limits = match root_limits.iter().find(|(root, _)| *root == project_root) {
+            Some((_, limits)) => limits.clone(),
+            None => {
+                let limits = resolve_limits(args, feature, args.config.open(&project_root)?.as_ref())?;
+                root_limits.push((project_root.clone(), limits.clone()));
+                limits
+            }
+        };
+        targets.push(each::DirTarget {
+            dir,
+            project_root,
+            relative,
+            limits,
+        });
+    }
+
+    if !args.skip_safety_check {
+        for target in &targets {
+            let build_dirs = out_of_tree_build_dirs(&target.project_root, std::slice::from_ref(&target.dir), &[]);
+            args.flagging_policy(&target.project_root, &build_dirs).check(&target.dir, &args.clean_cmd)?;
+        }
+    }
+
+    eprintln!("Cleaning {} directories with up to {} parallel jobs", targets.len(), jobs);
+    eprintln!();
+
+    let exec_options = executor::ExecOptions {
+        sandbox: args.sandbox,
+        ..Default::default()
+    };
+    let mut sources_before: Vec<(PathBuf, git_helper::TrackedState)> = Vec::new();
+    for target in &targets {
+        if sources_before.iter().any(|(root, _)| *root == target.project_root) {
+            continue;
+        }
+        if let Some(state) = args.source_snapshot(&target.project_root) {
+            sources_before.push((target.project_root.clone(), state));
+        }
+    }
+
+    let outcomes = each::run_each(targets, &args.clean_cmd, &exec_options, jobs);
+    each::print_summary(&outcomes);
+
+    for (project_root, before) in &sources_before {
+        check_sources(project_root, Some(before), args.restore_sources, Ok(()))?;
+    }
+
+    let failed: Vec<String> = outcomes
+        .iter()
+        .filter(|o| !o.succeeded())
+        .map(|o| o.target.dir.display().to_string())
+        .collect();
+    if !failed.is_empty() {
+        return Err(error::Error::DirectoriesFailed(failed));
+    }
+
+    // Group the cleaned directories by project root, keeping their order
+    let mut by_root: Vec<(PathBuf, Vec<each::DirOutcome>)> = Vec::new();
+    for outcome in outcomes {
+        match by_root.iter_mut().find(|(root, _)| *root == outcome.target.project_root) {
+            Some((_, root_outcomes)) => root_outcomes.push(outcome),
+            None => by_root.push((outcome.target.project_root.clone(), vec![outcome])),
+        }
+    }
+
+    let command_str = steps::join_command(&args.clean_cmd);
+    let mut saved_any = false;
+    for (project_root, root_outcomes) in by_root {
+        let root_steps: Vec<steps::CleanStep> = root_outcomes
+            .iter()
+            .map(|o| steps::CleanStep {
+                dir: o.target.relative.clone(),
+                command: args.clean_cmd.clone(),
+            })
+            .collect();
+        let store = args.config.open(&project_root)?;
+        let entries = match root_steps.as_slice() {
+            [step] => config_helper::single_command_entries(&step.dir, &command_str),
+            _ => config_helper::steps_entries(&root_steps)?,
+        };
+        if confirm_config_update(args, store.as_ref(), &entries, feature)? {
+            if let [step] = root_steps.as_slice() {
+                config_helper::save_config(store.as_ref(), &step.dir, &command_str, Some(feature))?;
+            } else {
+                config_helper::save_steps_config(store.as_ref(), &root_steps, Some(feature))?;
+            }
+            saved_any = true;
+        }
+        if !args.no_save {
+            save_cli_limits(args, feature, store.as_ref())?;
+        }
+
+        let mut record = history::RunRecord::new(feature);
+        for outcome in root_outcomes {
+            if let Ok(run) = outcome.result {
+                record.push_step(&outcome.target.relative, &command_str, run.stats);
+            }
+        }
+        history::save_record(&project_root, &record)?;
+
+        git_helper::auto_commit_if_modified(&project_root)?;
+    }
+
+    println!("\n✓ Clean command executed successfully in all directories.");
+    if saved_any {
+        println!("✓ Configuration saved.");
+    }
+    Ok(())
+}
+
+/// Move the files removed by the clean into the trash and prune old runs.
+///
+/// Trash handling is best-effort: failures are reported as warnings and do not
+/// change the outcome of the clean.
+fn finish_trash(project_root: &Path, snapshot: trash::TrashSnapshot, run_id: &str, args: &CommandArgs) {
+    match snapshot.finish() {
+        Ok(trashed) if !trashed.is_empty() => {
+            println!(
+                "Moved {} removed file(s) to {} (restore with: c2rust-clean restore {})",
+                trashed.len(),
+                trash::trash_dir(project_root).join(run_id).display(),
+                run_id
+            );
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Failed to move removed files to the trash: {}", e),
+    }
+
+    let max_age = std::time::Duration::from_secs(args.trash_max_age * 24 * 60 * 60);
+    match trash::prune(project_root, Some(max_age), Some(args.trash_max_size)) {
+        Ok(removed) if !removed.is_empty() => {
+            println!("Pruned {} old trashed run(s): {}", removed.len(), removed.join(", "));
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Failed to prune the trash: {}", e),
+    }
+}
+
+/// Artifact patterns for `verify`: the command line wins over the saved
+/// `clean.artifacts` list, which wins over the built-in defaults.
+fn artifact_patterns(cli: &[String], feature: &str, store: &dyn config_store::ConfigStore) -> Result<Vec<String>> {
+    if !cli.is_empty() {
+        return Ok(cli.to_vec());
+    }
+    if let Some(saved) = store.get("clean.artifacts", Some(feature))? {
+        return Ok(saved.split_whitespace().map(String::from).collect());
+    }
+    Ok(verify::DEFAULT_ARTIFACT_PATTERNS.iter().map(|p| p.to_string()).collect())
+}
+
+fn run_verify(args: VerifyArgs) -> Result<()> {
+    let feature = args.feature.as_deref().unwrap_or("default");
+    let project_root = locate_project_root(&std::env::current_dir()?, None)?;
+    let store = args.config.open(&project_root)?;
+
+    let dir = store.get("clean.dir", Some(feature))?.ok_or_else(|| {
+        error::Error::InvalidArgument(format!(
+            "no clean.dir saved for feature '{}', run `c2rust-clean clean` first",
+            feature
+        ))
+    })?;
+    let patterns = artifact_patterns(&args.artifacts, feature, store.as_ref())?;
+
+    println!("Verifying clean directory: {}", project_root.join(&dir).display());
+    verify::verify_dir(&project_root, &dir, &patterns)?;
+    println!("\n✓ {} is clean.", dir);
+    Ok(())
+}
+
+/// Print the clean settings of the requested features, flagging missing clean directories
+fn run_config_show(args: ConfigShowArgs) -> Result<()> {
+    let project_root = locate_project_root(&std::env::current_dir()?, None)?;
+    let store = args.config.open(&project_root)?;
+
+    let features = if args.all_features {
+        candidate_features(store.as_ref(), &project_root)?
+    } else {
+        vec![args.feature.clone().unwrap_or_else(|| "default".to_string())]
+    };
+
+    let mut configs = Vec::new();
+    for feature in &features {
+        let config = config_helper::FeatureConfig::load(store.as_ref(), feature, &project_root)?;
+        // With --all-features, only list features that have something saved
+        if !args.all_features || !config.is_empty() {
+            configs.push(config);
+        }
+    }
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&configs).map_err(std::io::Error::other)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    if configs.is_empty() {
+        println!("No clean configuration saved.");
+    }
+    for (i, config) in configs.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", config);
+    }
+    let missing = configs.iter().filter(|c| c.dir_exists == Some(false)).count();
+    if missing > 0 {
+        eprintln!("\nWarning: {} feature(s) have a clean.dir that no longer exists", missing);
+    }
+    Ok(())
+}
+
+fn run_feature(command: FeatureCommand) -> Result<()> {
+    let project_root = locate_project_root(&std::env::current_dir()?, None)?;
+    match command {
+        FeatureCommand::List { config } => {
+            let store = config.open(&project_root)?;
+            let mut found = false;
+            for feature in candidate_features(store.as_ref(), &project_root)? {
+                let saved = config_helper::FeatureConfig::load(store.as_ref(), &feature, &project_root)?;
+                if saved.is_empty() {
+                    continue;
+                }
+                found = true;
+                let summary = match (&saved.dir, &saved.cmd) {
+                    (Some(dir), Some(cmd)) => format!("{}: {}", dir, cmd),
+                    _ => "(partial clean configuration)".to_string(),
+                };
+                println!("{:<20} {}", feature, summary);
+            }
+            if !found {
+                println!("No feature has a saved clean configuration.");
+            }
+        }
+        FeatureCommand::Copy { src, dst, force, config } => {
+            let store = config.open(&project_root)?;
+            let source = config_helper::FeatureConfig::load(store.as_ref(), &src, &project_root)?;
+            if source.is_empty() {
+                return Err(error::Error::InvalidArgument(format!(
+                    "feature '{}' has no saved clean configuration",
+                    src
+                )));
+            }
+            let target = config_helper::FeatureConfig::load(store.as_ref(), &dst, &project_root)?;
+            if !target.is_empty() && !force {
+                return Err(error::Error::InvalidArgument(format!(
+                    "feature '{}' already has a clean configuration, pass --force to replace it",
+                    dst
+                )));
+            }
+            // Remove the destination's keys that the source does not set in the same batch
+            let mut entries = source.entries();
+            for saved in target.entries() {
+                if !entries.iter().any(|entry| entry.key == saved.key) {
+                    entries.push(config_store::ConfigEntry::unset(&saved.key));
+                }
+            }
+            store.set_all(&entries, Some(&dst))?;
+            git_helper::auto_commit_if_modified(&project_root)?;
+            println!("✓ Copied the clean configuration of '{}' to '{}'.", src, dst);
+        }
+        FeatureCommand::Delete { name, config } => {
+            let store = config.open(&project_root)?;
+            let saved = config_helper::FeatureConfig::load(store.as_ref(), &name, &project_root)?;
+            if saved.is_empty() {
+                return Err(error::Error::InvalidArgument(format!(
+                    "feature '{}' has no saved clean configuration",
+                    name
+                )));
+            }
+            // Remove every key in one batch, so a failure leaves the feature as it was
+            let entries: Vec<_> = saved
+                .entries()
+                .iter()
+                .map(|entry| config_store::ConfigEntry::unset(&entry.key))
+                .collect();
+            store.set_all(&entries, Some(&name))?;
+            let fingerprint = fingerprint::fingerprint_path(&project_root, &name);
+            if fingerprint.exists() {
+                std::fs::remove_file(fingerprint)?;
+            }
+            git_helper::auto_commit_if_modified(&project_root)?;
+            println!("✓ Deleted the clean configuration of '{}'.", name);
+        }
+    }
+    Ok(())
+}
+
+/// List the trashed runs, or restore the files of one run to their original location
+fn run_restore(args: RestoreArgs) -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    let project_root = locate_project_root(&current_dir, None)?;
+
+    let Some(run_id) = args.run_id else {
+        let entries = trash::list(&project_root)?;
+        if entries.is_empty() {
+            println!("Trash is empty.");
+        }
+        for entry in entries {
+            println!("{}  {} file(s), {} bytes", entry.run_id, entry.files, entry.bytes);
+        }
+        return Ok(());
+    };
+
+    let restored = trash::restore(&project_root, &run_id, args.force)?;
+    for path in &restored {
+        println!("Restored {}", path.display());
+    }
+    println!("\n✓ Restored {} file(s) from run {}.", restored.len(), run_id);
     Ok(())
 }
 
+/// Fail when the clean deleted or modified git-tracked files of `project_root`.
+///
+/// The check also runs when the clean itself failed; in that case the
+/// execution error is printed first and the source error is returned.
+fn check_sources<T>(
+    project_root: &Path,
+    before: Option<&git_helper::TrackedState>,
+    restore: bool,
+    result: Result<T>,
+) -> Result<T> {
+    if let Some(before) = before {
+        if let Err(source_error) = git_helper::check_sources_unchanged(project_root, before, restore) {
+            if let Err(e) = &result {
+                eprintln!("Error: {}", e);
+            }
+            return Err(source_error);
+        }
+    }
+    result
+}
+
 fn main() {
+    clap_complete::CompleteEnv::with_factory(Cli::command).complete();
     let cli = Cli::parse();
 
     let result = match cli.command {
-        Commands::Clean(args) => run(args),
+        Commands::Clean(args) => run(*args),
+        Commands::Restore(args) => run_restore(args),
+        Commands::Verify(args) => run_verify(args),
+        Commands::Config { command: ConfigCommand::Show(args) } => run_config_show(args),
+        Commands::Feature { command } => run_feature(command),
     };
 
     if let Err(e) = result {
@@ -0,0 +1,168 @@
+use crate::error::{Error, Result};
+use crate::executor::{capture_command, ExecOptions};
+use crate::safety::SafetyPolicy;
+use crate::steps::split_command;
+use std::path::{Path, PathBuf};
+
+/// A command `make -n` reports it would run
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedCommand {
+    /// Directory make would run the command in
+    pub dir: PathBuf,
+    pub line: String,
+    /// Why the command looks dangerous, if it does
+    pub warning: Option<String>,
+}
+
+/// Whether `command` invokes make
+pub fn is_make(command: &[String]) -> bool {
+    command
+        .first()
+        .and_then(|program| Path::new(program).file_name())
+        .is_some_and(|name| name == "make" || name == "gmake")
+}
+
+/// Run `make -n -w <args>` in `dir` and return the commands make would execute
+///
+/// Fails with [`Error::MakeDryRunFailed`] when make cannot build the requested
+/// target, most commonly because the Makefile has no `clean` target.
+pub fn dry_run(dir: &Path, command: &[String], options: &ExecOptions) -> Result<Vec<PlannedCommand>> {
+    let mut dry = vec![command[0].clone(), "-n".to_string(), "-w".to_string()];
+    dry.extend_from_slice(&command[1..]);
+
+    let run = capture_command(dir, &dry, options)?;
+    let output = String::from_utf8_lossy(&run.output);
+    if !run.status.success() {
+        return Err(Error::MakeDryRunFailed(describe_failure(dir, command, &output)));
+    }
+    Ok(parse_dry_run(&output, dir))
+}
+
+fn describe_failure(dir: &Path, command: &[String], output: &str) -> String {
+    let missing = output.lines().find_map(|line| {
+        let rest = line.split_once("No rule to make target ")?.1;
+        let target = rest.trim_start_matches(['\'', '`']);
+        Some(target.split(['\'', ',']).next().unwrap_or(target).to_string())
+    });
+    match missing {
+        Some(target) => format!(
+            "the Makefile in {} has no '{}' target. Check the target name, or pass \
+             --no-make-dry-run to run '{}' anyway",
+            dir.display(),
+            target,
+            command.join(" ")
+        ),
+        None => format!("'{} -n' failed in {}:\n{}", command.join(" "), dir.display(), output.trim_end()),
+    }
+}
+
+/// Parse the output of `make -n -w`, following `Entering directory` messages
+pub fn parse_dry_run(output: &str, dir: &Path) -> Vec<PlannedCommand> {
+    let mut dirs = vec![dir.to_path_buf()];
+    let mut commands = Vec::new();
+
+    for line in output.lines() {
+        if let Some(message) = make_message(line) {
+            if let Some(entered) = message.strip_prefix("Entering directory ") {
+                dirs.push(PathBuf::from(entered.trim_matches(['\'', '`'])));
+            } else if message.starts_with("Leaving directory ") && dirs.len() > 1 {
+                dirs.pop();
+            }
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        commands.push(PlannedCommand {
+            dir: dirs.last().cloned().unwrap_or_default(),
+            line: line.to_string(),
+            warning: None,
+        });
+    }
+    commands
+}
+
+/// The text after `make: ` / `make[N]: ` for make's own messages
+fn make_message(line: &str) -> Option<&str> {
+    let (prefix, message) = line.split_once(": ")?;
+    let name = prefix.split('[').next()?;
+    let is_make = Path::new(name).file_name().is_some_and(|n| n == "make" || n == "gmake");
+    let level_ok = prefix[name.len()..].is_empty()
+        || (prefix[name.len()..].starts_with('[') && prefix.ends_with(']'));
+    (is_make && level_ok).then_some(message)
+}
+
+/// Mark planned commands that the safety policy rejects, or that run outside the project root
+pub fn flag_dangerous(commands: &mut [PlannedCommand], policy: &SafetyPolicy) {
+    for planned in commands.iter_mut() {
+        if !policy.is_inside(&planned.dir) {
+            planned.warning = Some(format!("runs in {} outside the project root", planned.dir.display()));
+            continue;
+        }
+        let Ok(tokens) = split_command(&planned.line) else {
+            continue;
+        };
+        if let Err(Error::UnsafeCommand(reason)) = policy.check_script(&planned.dir, &tokens) {
+            planned.warning = Some(reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "make: Entering directory '/p/build'\n\
+                          rm -f *.o\n\
+                          make -C sub clean\n\
+                          make[1]: Entering directory '/p/build/sub'\n\
+                          cd .. && rm -rf ../..\n\
+                          make[1]: Leaving directory '/p/build/sub'\n\
+                          make[1]: Entering directory '/elsewhere'\n\
+                          rm -f x.o\n\
+                          make[1]: Leaving directory '/elsewhere'\n\
+                          make: Leaving directory '/p/build'\n";
+
+    #[test]
+    fn test_is_make() {
+        let cmd = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert!(is_make(&cmd("make clean")));
+        assert!(is_make(&cmd("/usr/bin/gmake -j4 clean")));
+        assert!(!is_make(&cmd("cmake --build . --target clean")));
+    }
+
+    #[test]
+    fn test_parse_and_flag() {
+        let mut commands = parse_dry_run(OUTPUT, Path::new("/p/build"));
+        let lines: Vec<(&str, &str)> = commands
+            .iter()
+            .map(|c| (c.dir.to_str().unwrap(), c.line.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("/p/build", "rm -f *.o"),
+                ("/p/build", "make -C sub clean"),
+                ("/p/build/sub", "cd .. && rm -rf ../.."),
+                ("/elsewhere", "rm -f x.o"),
+            ]
+        );
+
+        let policy = SafetyPolicy::new(Path::new("/p"), &[]);
+        flag_dangerous(&mut commands, &policy);
+        assert!(commands[0].warning.is_none());
+        assert!(commands[1].warning.is_none());
+        assert!(commands[2].warning.as_deref().unwrap().contains("outside the project root"));
+        assert!(commands[3].warning.as_deref().unwrap().contains("/elsewhere"));
+    }
+
+    #[test]
+    fn test_missing_target_message() {
+        let message = describe_failure(
+            Path::new("/p/build"),
+            &["make".to_string(), "clean".to_string()],
+            "make: Entering directory '/p/build'\nmake: *** No rule to make target 'clean'.  Stop.\n",
+        );
+        assert!(message.contains("has no 'clean' target"), "{}", message);
+    }
+}
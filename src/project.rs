@@ -0,0 +1,104 @@
+use crate::build_dir;
+use crate::error::{Error, Result};
+use crate::safety;
+use std::path::{Path, PathBuf};
+
+/// Version control and c2rust metadata directories, which are never scanned,
+/// fingerprinted or moved to the trash as part of a clean directory
+pub const SKIPPED_DIRS: [&str; 2] = [".git", ".c2rust"];
+
+/// Find the project root directory by searching for marker files/directories.
+/// Searches upward from start_dir for directories containing:
+/// - .git directory (Git repository root)
+/// - Cargo.toml (Rust project root)
+/// - .c2rust directory (c2rust project marker)
+///
+/// If none found, returns the start_dir as root.
+pub fn find_project_root(start_dir: &Path) -> Result<PathBuf> {
+    let mut current = start_dir;
+    
+    // List of marker files/directories that indicate a project root
+    let markers = [".git", "Cargo.toml", ".c2rust"];
+    
+    loop {
+        // Check if any marker exists in the current directory
+        for marker in &markers {
+            let marker_path = current.join(marker);
+            if marker_path.exists() {
+                return Ok(current.to_path_buf());
+            }
+        }
+        
+        // Move to parent directory
+        match current.parent() {
+            Some(parent) => current = parent,
+            None => return Ok(start_dir.to_path_buf()),
+        }
+    }
+}
+
+/// Find the project root for a clean run in `dir`.
+///
+/// Out-of-tree CMake and Meson build directories are resolved through the
+/// source directory recorded in `CMakeCache.txt` or `meson-info`, unless the
+/// source directory is given explicitly.
+pub fn locate_project_root(dir: &Path, source_dir: Option<&Path>) -> Result<PathBuf> {
+    if let Some(source_dir) = source_dir {
+        let source_dir = source_dir.canonicalize().map_err(|e| {
+            Error::InvalidArgument(format!("source directory {}: {}", source_dir.display(), e))
+        })?;
+        if let Some(info) = build_dir::detect(dir).filter(|info| !info.belongs_to(&source_dir)) {
+            eprintln!(
+                "Warning: {} was configured by {} for {}, not for {}",
+                dir.display(),
+                info.system,
+                info.source_dir.display(),
+                source_dir.display()
+            );
+        }
+        return find_project_root(&source_dir);
+    }
+
+    match build_dir::detect(dir) {
+        Some(info) if !safety::normalize(dir).starts_with(safety::normalize(&info.source_dir)) => {
+            if !info.source_dir.is_dir() {
+                return Err(Error::InvalidArgument(format!(
+                    "source directory {} recorded in the {} build directory {} does not exist",
+                    info.source_dir.display(),
+                    info.system,
+                    dir.display()
+                )));
+            }
+            eprintln!(
+                "Out-of-tree {} build directory, source directory: {}",
+                info.system,
+                info.source_dir.display()
+            );
+            find_project_root(&info.source_dir)
+        }
+        _ => find_project_root(dir),
+    }
+}
+
+/// Clean directories outside `project_root` that may still be cleaned: CMake
+/// or Meson build directories configured for the project, plus `explicit` ones.
+/// Other directories outside the root only get a warning, so destructive
+/// commands in them are still rejected by the safety check.
+pub fn out_of_tree_build_dirs(project_root: &Path, dirs: &[PathBuf], explicit: &[PathBuf]) -> Vec<PathBuf> {
+    let mut allowed = Vec::new();
+    for dir in dirs {
+        if safety::normalize(dir).starts_with(project_root) || allowed.contains(dir) {
+            continue;
+        }
+        let recognized = build_dir::detect(dir).is_some_and(|info| info.belongs_to(project_root));
+        if recognized || explicit.contains(dir) {
+            allowed.push(dir.clone());
+        } else {
+            eprintln!(
+                "Warning: clean directory {} is outside the project root and is not a CMake or Meson build directory of it",
+                dir.display()
+            );
+        }
+    }
+    allowed
+}
@@ -0,0 +1,304 @@
+use crate::executor::{ProcessRunner, RunStats};
+use crate::limits::ResourceLimits;
+use crate::steps::join_command;
+use crate::tracer::DeletionEvent;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A process to start, and how to start it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandSpec {
+    /// Program followed by its arguments
+    pub argv: Vec<String>,
+    /// Working directory; the current directory when `None`
+    pub dir: Option<PathBuf>,
+    /// Let the process read our stdin (otherwise it reads from /dev/null)
+    pub inherit_stdin: bool,
+    /// Attach the process to a pseudo-terminal; all its output arrives as stdout
+    pub pty: bool,
+    pub limits: ResourceLimits,
+    /// Run in a namespace sandbox where only `dir` and a private `/tmp` are writable
+    pub sandbox: bool,
+    /// Trace the `unlink`/`rmdir`/`rename` calls of the process and its descendants
+    pub trace_deletions: bool,
+    /// Start a new process group, so that a timeout also kills the children
+    pub process_group: bool,
+}
+
+impl CommandSpec {
+    pub fn new<I, S>(argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        CommandSpec {
+            argv: argv.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn in_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+}
+
+impl std::fmt::Display for CommandSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&join_command(&self.argv))
+    }
+}
+
+/// Which stream a chunk of output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Receives output chunk by chunk as it arrives, possibly from several threads
+pub type OutputSink = Arc<dyn Fn(OutputStream, &[u8]) -> std::io::Result<()> + Send + Sync>;
+
+/// Where the output of a started process goes
+#[derive(Clone)]
+pub enum Output {
+    /// Straight to our own stdout and stderr, so a terminal stays a terminal
+    Inherit,
+    /// To the sink, with stdout and stderr kept apart
+    Stream(OutputSink),
+    /// To the sink as stdout, through a single pipe that keeps the original interleaving
+    Merged(OutputSink),
+}
+
+/// How a process ended
+#[derive(Debug)]
+pub struct Finished {
+    /// `None` when the process was killed after the timeout
+    pub status: Option<ExitStatus>,
+    pub stats: RunStats,
+    /// Deletions seen when [`CommandSpec::trace_deletions`] was set
+    pub deletions: Vec<DeletionEvent>,
+}
+
+/// Starts processes for the clean commands, `make -n` and c2rust-config
+pub trait CommandRunner: Send + Sync {
+    /// Start `command`, passing its output to `output` as it arrives
+    fn spawn(&self, command: &CommandSpec, output: Output) -> std::io::Result<Box<dyn RunningProcess>>;
+
+    /// Whether this runner starts the real programs, so that what it learns
+    /// about them (such as the c2rust-config probe) may be cached across runs
+    fn starts_real_processes(&self) -> bool {
+        false
+    }
+
+    /// Check that commands can run with [`CommandSpec::sandbox`] in `dir`
+    ///
+    /// The default accepts any directory and leaves the sandbox to [`spawn`](Self::spawn).
+    fn probe_sandbox(&self, _dir: &Path) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A process started by a [`CommandRunner`]
+pub trait RunningProcess {
+    /// Wait until the process has exited and all its output was delivered,
+    /// killing it once `timeout` has passed
+    fn wait(self: Box<Self>, timeout: Option<Duration>) -> std::io::Result<Finished>;
+}
+
+/// A shareable [`CommandRunner`]; the default one starts real processes
+#[derive(Clone)]
+pub struct Runner(Arc<dyn CommandRunner>);
+
+impl Runner {
+    pub fn new(runner: impl CommandRunner + 'static) -> Self {
+        Runner(Arc::new(runner))
+    }
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner::new(ProcessRunner)
+    }
+}
+
+impl std::fmt::Debug for Runner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Runner")
+    }
+}
+
+impl std::ops::Deref for Runner {
+    type Target = dyn CommandRunner;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Scripted outcome of a command started by a [`RecordingRunner`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Response {
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl Response {
+    pub fn success(stdout: impl Into<Vec<u8>>) -> Self {
+        Response {
+            stdout: stdout.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn failure(exit_code: i32, stderr: impl Into<Vec<u8>>) -> Self {
+        Response {
+            exit_code,
+            stderr: stderr.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A [`CommandRunner`] that starts nothing and records every command instead
+///
+/// A command gets the response of the most recently added rule whose
+/// arguments it contains, and succeeds without output otherwise. Clones
+/// share the record, so one clone can go into a [`Runner`] while another
+/// is inspected afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingRunner {
+    state: Arc<Mutex<Recording>>,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    commands: Vec<CommandSpec>,
+    rules: Vec<(Vec<String>, Response)>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer commands whose arguments contain `args` (adjacent, in order) with `response`
+    pub fn respond(&self, args: &[&str], response: Response) {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        self.state.lock().unwrap().rules.push((args, response));
+    }
+
+    /// Every command started so far, in order
+    pub fn commands(&self) -> Vec<CommandSpec> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    /// The command lines of [`commands`](Self::commands), quoted like a shell needs them
+    pub fn command_lines(&self) -> Vec<String> {
+        self.commands().iter().map(CommandSpec::to_string).collect()
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn spawn(&self, command: &CommandSpec, output: Output) -> std::io::Result<Box<dyn RunningProcess>> {
+        let mut state = self.state.lock().unwrap();
+        state.commands.push(command.clone());
+        let response = state
+            .rules
+            .iter()
+            .rev()
+            .find(|(args, _)| args.is_empty() || command.argv.windows(args.len()).any(|window| window == args))
+            .map(|(_, response)| response.clone())
+            .unwrap_or_default();
+        Ok(Box::new(RecordedProcess { response, output }))
+    }
+}
+
+struct RecordedProcess {
+    response: Response,
+    output: Output,
+}
+
+impl RunningProcess for RecordedProcess {
+    fn wait(self: Box<Self>, _timeout: Option<Duration>) -> std::io::Result<Finished> {
+        let chunks = [
+            (OutputStream::Stdout, &self.response.stdout),
+            (OutputStream::Stderr, &self.response.stderr),
+        ];
+        for (stream, data) in chunks.into_iter().filter(|(_, data)| !data.is_empty()) {
+            match (&self.output, stream) {
+                (Output::Inherit, OutputStream::Stdout) => std::io::stdout().write_all(data)?,
+                (Output::Inherit, OutputStream::Stderr) => std::io::stderr().write_all(data)?,
+                (Output::Stream(sink), _) => sink(stream, data)?,
+                (Output::Merged(sink), _) => sink(OutputStream::Stdout, data)?,
+            }
+        }
+        Ok(Finished {
+            status: Some(exit_status(self.response.exit_code)),
+            stats: RunStats::default(),
+            deletions: Vec::new(),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_runner_records_and_responds() {
+        let recorder = RecordingRunner::new();
+        recorder.respond(&["--get", "clean.cmd"], Response::success("make clean\n"));
+        recorder.respond(&["--set"], Response::failure(2, "read-only"));
+        let runner = Runner::new(recorder.clone());
+
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let sink: OutputSink = {
+            let collected = Arc::clone(&collected);
+            Arc::new(move |stream, data: &[u8]| {
+                collected.lock().unwrap().push((stream, String::from_utf8_lossy(data).into_owned()));
+                Ok(())
+            })
+        };
+
+        let get = CommandSpec::new(["c2rust-config", "config", "--get", "clean.cmd"]).in_dir("/p");
+        let finished = runner.spawn(&get, Output::Stream(sink.clone())).unwrap().wait(None).unwrap();
+        assert!(finished.status.unwrap().success());
+
+        let set = CommandSpec::new(["c2rust-config", "config", "--set", "clean.cmd", "make clean"]);
+        let finished = runner.spawn(&set, Output::Stream(sink)).unwrap().wait(None).unwrap();
+        assert_eq!(finished.status.unwrap().code(), Some(2));
+
+        assert_eq!(
+            recorder.command_lines(),
+            vec![
+                "c2rust-config config --get clean.cmd",
+                "c2rust-config config --set clean.cmd 'make clean'"
+            ]
+        );
+        assert_eq!(recorder.commands()[0].dir.as_deref(), Some(std::path::Path::new("/p")));
+        assert_eq!(
+            *collected.lock().unwrap(),
+            vec![
+                (OutputStream::Stdout, "make clean\n".to_string()),
+                (OutputStream::Stderr, "read-only".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use crate::error::{Error, Result};
+use crate::git_helper;
+use crate::steps::{split_command, split_simple_commands};
+use std::path::{Component, Path, PathBuf};
+
+/// Paths under the project root that clean commands must never remove
+pub const DEFAULT_PROTECTED: [&str; 2] = [".c2rust", ".git"];
+
+/// Pre-execution check that rejects destructive clean commands whose targets
+/// escape the project root or hit protected paths
+///
+/// Source directories, i.e. directories holding git-tracked C sources or
+/// headers, are protected as well: they may not be removed as a whole, while
+/// build outputs inside them can still be deleted.
+///
+/// Only well-known destructive programs are analysed (`rm`, `find` with
+/// `-delete` or `-exec rm`, and `git clean`); every other command is allowed.
+/// Wrappers such as `env`, `sudo`, `nice` and `xargs` are looked through, and
+/// the script of `sh -c`/`bash -c` is checked command by command. This is a
+/// heuristic guard against typos such as `rm -rf ../..`, not a sandbox.
+#[derive(Debug, Clone)]
+pub struct SafetyPolicy {
+    project_root: PathBuf,
+    /// Directories outside the project root that may be cleaned as well
+    allowed: Vec<PathBuf>,
+    protected: Vec<PathBuf>,
+    source_dirs: Vec<PathBuf>,
+}
+
+/// How a destructive command treats the paths it is given
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TargetKind {
+    /// The target and everything below it is removed (`rm`, `git clean`)
+    Tree,
+    /// Only matching entries below the target are removed (`find -name ... -delete`)
+    Filtered,
+    /// Everything below the target that git does not track is removed (`git clean`)
+    Untracked,
+}
+
+impl SafetyPolicy {
+    /// Protect the defaults, the source directories of the project's repository
+    /// and `extra` paths (relative to the project root or absolute)
+    pub fn new(project_root: &Path, extra: &[PathBuf]) -> Self {
+        let root = normalize(project_root);
+        let source_dirs = git_helper::source_dirs(&root)
+            .iter()
+            .map(|dir| normalize(&root.join(dir)))
+            .collect();
+        let protected = DEFAULT_PROTECTED
+            .iter()
+            .map(PathBuf::from)
+            .chain(extra.iter().cloned())
+            .map(|p| normalize(&root.join(p)))
+            .collect();
+        SafetyPolicy {
+            project_root: root,
+            allowed: Vec::new(),
+            protected,
+            source_dirs,
+        }
+    }
+
+    /// Also accept targets inside `dir`, e.g. an out-of-tree build directory
+    pub fn allow_dir(mut self, dir: &Path) -> Self {
+        self.allowed.push(normalize(dir));
+        self
+    }
+
+    /// Whether `path` is inside the project root or an allowed directory
+    pub fn is_inside(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        path.starts_with(&self.project_root) || self.allowed.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Check `command`, which will run in `dir`, against the policy
+    pub fn check(&self, dir: &Path, command: &[String]) -> Result<()> {
+        let command = strip_wrappers(command);
+        if let Some(script) = shell_script(command) {
+            let tokens = split_command(script).map_err(|e| {
+                Error::UnsafeCommand(format!(
+                    "refusing to run '{}': cannot analyse the shell script ({}). Use --i-know-what-im-doing to override",
+                    command.join(" "),
+                    e
+                ))
+            })?;
+            return self.check_script(dir, &tokens);
+        }
+
+        let Some((kind, base, targets)) = destructive_targets(dir, command) else {
+            return Ok(());
+        };
+
+        for target in targets {
+            let path = normalize(&base.join(&target));
+            if let Some(reason) = self.violation(&path, kind) {
+                return Err(Error::UnsafeCommand(format!(
+                    "refusing to run '{}': target '{}' {}. Use --i-know-what-im-doing to override",
+                    command.join(" "),
+                    target,
+                    reason
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every simple command of a tokenized shell command line, following `cd` along the way
+    pub fn check_script(&self, dir: &Path, tokens: &[String]) -> Result<()> {
+        let mut dir = dir.to_path_buf();
+        for simple in split_simple_commands(tokens) {
+            if simple.first().map(String::as_str) == Some("cd") {
+                if let Some(target) = simple.get(1) {
+                    dir = dir.join(target);
+                }
+                continue;
+            }
+            self.check(&dir, &simple)?;
+        }
+        Ok(())
+    }
+
+    fn violation(&self, path: &Path, kind: TargetKind) -> Option<String> {
+        if !self.is_inside(path) {
+            return Some(format!(
+                "resolves to {} outside the project root {}",
+                path.display(),
+                self.project_root.display()
+            ));
+        }
+        if let Some(protected) = self.protected.iter().find(|p| path.starts_with(p)) {
+            return Some(format!("is inside the protected path {}", protected.display()));
+        }
+        if kind != TargetKind::Filtered {
+            if let Some(protected) = self.protected.iter().find(|p| p.starts_with(path)) {
+                return Some(format!("would also remove the protected path {}", protected.display()));
+            }
+        }
+        if kind == TargetKind::Tree {
+            if let Some(source_dir) = self.source_dirs.iter().find(|dir| dir.starts_with(path)) {
+                return Some(format!("would remove the source directory {}", source_dir.display()));
+            }
+        }
+        None
+    }
+}
+
+/// Drop leading programs that run the rest of their arguments as a command,
+/// together with their own options (`env FOO=1 nice -n 5 rm ...` becomes `rm ...`)
+fn strip_wrappers(mut command: &[String]) -> &[String] {
+    loop {
+        let Some(program) = command.first().and_then(|p| Path::new(p).file_name()).and_then(|p| p.to_str()) else {
+            return command;
+        };
+        let args = &command[1..];
+        let skip = match program {
+            "env" => {
+                let skip = skip_options(args, &["-u", "--unset", "-C", "--chdir", "-S", "--split-string"]);
+                skip + args[skip..].iter().take_while(|arg| arg.contains('=')).count()
+            }
+            "sudo" | "doas" => skip_options(
+                args,
+                &["-u", "-g", "-h", "-p", "-U", "-C", "-D", "-r", "-t", "-T", "--user", "--group", "--chdir"],
+            ),
+            "nice" => skip_options(args, &["-n", "--adjustment"]),
+            "nohup" | "time" | "command" | "exec" => skip_options(args, &[]),
+            "xargs" => skip_options(
+                args,
+                &["-I", "-n", "-P", "-L", "-d", "-E", "-s", "-a", "--arg-file", "--delimiter", "--max-args", "--max-procs"],
+            ),
+            _ => return command,
+        };
+        command = &args[skip..];
+    }
+}
+
+/// Number of leading options in `args`, counting the values of `with_value` options and a final `--`
+fn skip_options(args: &[String], with_value: &[&str]) -> usize {
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if arg == "--" {
+            return i + 1;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            break;
+        }
+        i += if with_value.contains(&arg.as_str()) { 2 } else { 1 };
+    }
+    i.min(args.len())
+}
+
+/// The script of `sh -c SCRIPT` (or bash, dash, zsh, ksh), if `command` is one
+fn shell_script(command: &[String]) -> Option<&str> {
+    let program = Path::new(command.first()?).file_name()?.to_str()?;
+    if !matches!(program, "sh" | "bash" | "dash" | "zsh" | "ksh") {
+        return None;
+    }
+    let mut has_c = false;
+    for arg in &command[1..] {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.starts_with('-') && !flags.is_empty() => has_c |= flags.contains('c'),
+            _ => return has_c.then_some(arg.as_str()),
+        }
+    }
+    None
+}
+
+/// Find the paths a destructive command would delete, relative to the returned base dir
+fn destructive_targets(dir: &Path, command: &[String]) -> Option<(TargetKind, PathBuf, Vec<String>)> {
+    let program = Path::new(command.first()?).file_name()?.to_str()?;
+    let args = &command[1..];
+
+    match program {
+        "rm" => Some((TargetKind::Tree, dir.to_path_buf(), operands(args))),
+        "find" => find_targets(args).map(|(kind, targets)| (kind, dir.to_path_buf(), targets)),
+        "git" => git_clean_targets(dir, args),
+        _ => None,
+    }
+}
+
+/// Non-option arguments, treating everything after `--` as an operand
+fn operands(args: &[String]) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut only_operands = false;
+    for arg in args {
+        if only_operands || arg == "-" || !arg.starts_with('-') {
+            result.push(arg.clone());
+        } else if arg == "--" {
+            only_operands = true;
+        }
+    }
+    result
+}
+
+fn find_targets(args: &[String]) -> Option<(TargetKind, Vec<String>)> {
+    let deletes = args.iter().enumerate().any(|(i, arg)| {
+        arg == "-delete"
+            || (matches!(arg.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir")
+                && args
+                    .get(i + 1)
+                    .and_then(|p| Path::new(p).file_name())
+                    .is_some_and(|p| p == "rm" || p == "rmdir"))
+    });
+    if !deletes {
+        return None;
+    }
+
+    // Skip find's own leading options, then collect starting points up to the expression
+    let start = args
+        .iter()
+        .position(|a| !matches!(a.as_str(), "-H" | "-L" | "-P"))
+        .unwrap_or(args.len());
+    let mut starting_points: Vec<String> = args[start..]
+        .iter()
+        .take_while(|a| !a.starts_with('-') && *a != "(" && *a != "!")
+        .cloned()
+        .collect();
+    if starting_points.is_empty() {
+        starting_points.push(".".to_string());
+    }
+
+    let filtered = args.iter().any(|a| {
+        matches!(
+            a.as_str(),
+            "-name" | "-iname" | "-path" | "-ipath" | "-wholename" | "-regex" | "-iregex"
+        )
+    });
+    let kind = if filtered { TargetKind::Filtered } else { TargetKind::Tree };
+    Some((kind, starting_points))
+}
+
+fn git_clean_targets(dir: &Path, args: &[String]) -> Option<(TargetKind, PathBuf, Vec<String>)> {
+    let mut base = dir.to_path_buf();
+    let mut rest = args;
+    // Handle global options before the subcommand, most importantly `-C <path>`
+    loop {
+        match rest.first().map(String::as_str) {
+            Some("-C") => {
+                base = base.join(rest.get(1)?);
+                rest = &rest[2..];
+            }
+            Some("-c") => rest = rest.get(2..)?,
+            Some(opt) if opt.starts_with('-') => rest = &rest[1..],
+            _ => break,
+        }
+    }
+    if rest.first().map(String::as_str) != Some("clean") {
+        return None;
+    }
+
+    // Options taking a separate value must not be mistaken for pathspecs
+    let mut pathspecs = Vec::new();
+    let mut iter = rest[1..].iter();
+    let mut only_operands = false;
+    while let Some(arg) = iter.next() {
+        if only_operands || !arg.starts_with('-') {
+            pathspecs.push(arg.clone());
+        } else if arg == "--" {
+            only_operands = true;
+        } else if arg == "-e" || arg == "--exclude" {
+            iter.next();
+        }
+    }
+    if pathspecs.is_empty() {
+        pathspecs.push(".".to_string());
+    }
+    Some((TargetKind::Untracked, base, pathspecs))
+}
+
+/// Lexically normalize a path: resolve `.` and `..` without touching the filesystem
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    fn policy() -> SafetyPolicy {
+        SafetyPolicy::new(Path::new("/project"), &[PathBuf::from("src")])
+    }
+
+    #[test]
+    fn test_rm_inside_root_allowed() {
+        let build = Path::new("/project/build");
+        assert!(policy().check(build, &cmd("rm -rf obj *.o")).is_ok());
+        assert!(policy().check(build, &cmd("make clean")).is_ok());
+    }
+
+    #[test]
+    fn test_rm_escaping_root_rejected() {
+        let build = Path::new("/project/build");
+        for bad in ["rm -rf /", "rm -rf ../..", "rm -f -- ../../etc/passwd", "/bin/rm -r ../src"] {
+            assert!(
+                matches!(policy().check(build, &cmd(bad)), Err(Error::UnsafeCommand(_))),
+                "expected '{}' to be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_rm_of_root_rejected() {
+        // Removing the root would remove .c2rust and .git as well
+        let root = Path::new("/project");
+        assert!(policy().check(root, &cmd("rm -rf .")).is_err());
+        assert!(policy().check(root, &cmd("rm -rf .c2rust/cache")).is_err());
+    }
+
+    #[test]
+    fn test_find_delete() {
+        let root = Path::new("/project");
+        assert!(policy().check(root, &cmd("find . -name *.o -delete")).is_ok());
+        assert!(policy().check(root, &cmd("find . -delete")).is_err());
+        assert!(policy().check(root, &cmd("find .. -name *.o -delete")).is_err());
+        assert!(policy().check(root, &cmd("find / -name *.o -exec rm {} ;")).is_err());
+        assert!(policy().check(root, &cmd("find / -name *.o -print")).is_ok());
+    }
+
+    #[test]
+    fn test_git_clean() {
+        assert!(policy().check(Path::new("/project/build"), &cmd("git clean -fdx")).is_ok());
+        assert!(policy().check(Path::new("/project"), &cmd("git clean -fdx")).is_err());
+        assert!(policy().check(Path::new("/project"), &cmd("git -C build clean -fdx -e keep")).is_ok());
+        assert!(policy().check(Path::new("/project"), &cmd("git status")).is_ok());
+    }
+
+    #[test]
+    fn test_source_dirs_protected_by_default() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let repo = git2::Repository::init(root).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join("src/main.c"), "int main;").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("src/main.c")).unwrap();
+        index.write().unwrap();
+
+        let policy = SafetyPolicy::new(root, &[]);
+        assert!(matches!(policy.check(root, &cmd("rm -rf src")), Err(Error::UnsafeCommand(_))));
+        assert!(policy.check(&root.join("src"), &cmd("rm -rf .")).is_err());
+        assert!(policy.check(root, &cmd("find src -delete")).is_err());
+        // Build outputs next to the sources can still be removed
+        assert!(policy.check(root, &cmd("rm -f src/main.o")).is_ok());
+        assert!(policy.check(root, &cmd("find src -name *.o -delete")).is_ok());
+        assert!(policy.check(&root.join("src"), &cmd("git clean -fdx")).is_ok());
+        assert!(policy.check(root, &cmd("rm -rf build")).is_ok());
+    }
+
+    #[test]
+    fn test_wrappers_and_shell_scripts_are_checked() {
+        let build = Path::new("/project/build");
+        let shell = |script: &str| vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        for bad in ["env rm -rf /", "sudo -u root rm -rf ../..", "nice -n 5 rm -rf /", "env FOO=1 nice xargs rm -rf /"] {
+            assert!(policy().check(build, &cmd(bad)).is_err(), "expected '{}' to be rejected", bad);
+        }
+        assert!(policy().check(build, &shell("rm -rf ../..")).is_err());
+        assert!(policy().check(build, &shell("make clean && cd .. && rm -rf ..")).is_err());
+        assert!(policy().check(build, &["bash".to_string(), "-ec".to_string(), "env rm -rf /".to_string()]).is_err());
+        assert!(policy().check(build, &shell("rm -f 'unterminated")).is_err());
+
+        assert!(policy().check(build, &cmd("env CFLAGS=-O2 rm -f *.o")).is_ok());
+        assert!(policy().check(build, &shell("rm -rf obj; cd obj2 && rm -f *.o")).is_ok());
+        assert!(policy().check(build, &cmd("sh ./clean.sh")).is_ok());
+    }
+
+    #[test]
+    fn test_allowed_build_dir() {
+        let policy = policy().allow_dir(Path::new("/build"));
+        assert!(policy.check(Path::new("/build"), &cmd("rm -rf CMakeFiles")).is_ok());
+        assert!(policy.check(Path::new("/build"), &cmd("rm -rf ../etc")).is_err());
+    }
+}
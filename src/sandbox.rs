@@ -0,0 +1,427 @@
+//! Linux namespace sandbox for clean commands.
+//!
+//! The child is moved into a new user + mount namespace in which every mount
+//! is remounted read-only, except for a bind mount of the clean directory and
+//! a fresh tmpfs on `/tmp`. `.c2rust` and `.git` inside the clean directory
+//! are bound back over it read-only. `/dev`, `/proc` and `/sys` are left
+//! untouched so ordinary tools keep working.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Prepared sandbox for running a command with write access limited to one directory
+///
+/// Everything that needs memory allocation is computed up front, since the
+/// namespace setup runs between `fork` and `exec`.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    #[cfg(target_os = "linux")]
+    inner: linux::Setup,
+}
+
+impl Sandbox {
+    /// Prepare a sandbox in which only `writable_dir` (and `/tmp`) can be written,
+    /// except for the protected `.c2rust` and `.git` directories inside it
+    pub fn new(writable_dir: &Path) -> std::io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            Ok(Sandbox {
+                inner: linux::Setup::new(writable_dir)?,
+            })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = writable_dir;
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "namespace sandboxing is only available on Linux",
+            ))
+        }
+    }
+
+    /// Check that the namespaces can actually be created on this system
+    ///
+    /// Runs the full setup in a throw-away child that exits before `exec`,
+    /// so failures (for example disabled unprivileged user namespaces) are
+    /// detected without running the clean command.
+    pub fn probe(&self) -> std::io::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            self.inner.probe()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Ok(())
+        }
+    }
+
+    /// Arrange for `cmd` to run inside the sandbox
+    pub fn apply(&self, cmd: &mut Command) {
+        #[cfg(target_os = "linux")]
+        self.inner.apply(cmd);
+        #[cfg(not(target_os = "linux"))]
+        let _ = cmd;
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use crate::safety::DEFAULT_PROTECTED;
+
+    /// Mount points that are left as they are inside the sandbox
+    const UNTOUCHED_MOUNTS: [&str; 3] = ["/dev", "/proc", "/sys"];
+
+    #[derive(Debug, Clone)]
+    pub struct Setup {
+        writable_dir: CString,
+        /// Existing protected directories inside `writable_dir` that stay read-only,
+        /// with the flags that must be preserved
+        protected_dirs: Vec<(CString, libc::c_ulong)>,
+        /// Parent directories of `writable_dir` to recreate inside the tmpfs on /tmp
+        tmp_parents: Option<Vec<CString>>,
+        /// Mount points to remount read-only, with the flags that must be preserved
+        readonly_mounts: Vec<(CString, libc::c_ulong)>,
+        uid_map: CString,
+        gid_map: CString,
+    }
+
+    impl Setup {
+        pub fn new(writable_dir: &Path) -> std::io::Result<Self> {
+            let writable_dir = writable_dir.canonicalize()?;
+            let tmp = Path::new("/tmp");
+
+            let tmp_parents = writable_dir.starts_with(tmp).then(|| {
+                writable_dir
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|p| p.starts_with(tmp) && *p != tmp)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .map(cstring)
+                    .collect::<std::io::Result<Vec<_>>>()
+            });
+
+            let mut protected_dirs = Vec::new();
+            for name in DEFAULT_PROTECTED {
+                let dir = writable_dir.join(name);
+                if dir.is_dir() {
+                    let c_path = cstring(&dir)?;
+                    let flags = locked_flags(&c_path);
+                    protected_dirs.push((c_path, flags));
+                }
+            }
+
+            let mut readonly_mounts = Vec::new();
+            for mount_point in mount_points()? {
+                if mount_point.starts_with(&writable_dir)
+                    || mount_point.starts_with(tmp)
+                    || UNTOUCHED_MOUNTS.iter().any(|m| mount_point.starts_with(m))
+                {
+                    continue;
+                }
+                let c_path = cstring(&mount_point)?;
+                let flags = locked_flags(&c_path);
+                readonly_mounts.push((c_path, flags));
+            }
+
+            // SAFETY: getuid/getgid cannot fail
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Ok(Setup {
+                writable_dir: cstring(&writable_dir)?,
+                protected_dirs,
+                tmp_parents: tmp_parents.transpose()?,
+                readonly_mounts,
+                uid_map: CString::new(format!("{} {} 1\n", uid, uid)).unwrap(),
+                gid_map: CString::new(format!("{} {} 1\n", gid, gid)).unwrap(),
+            })
+        }
+
+        pub fn probe(&self) -> std::io::Result<()> {
+            let setup = self.clone();
+            let mut cmd = Command::new("/");
+            // SAFETY: `enter` only performs async-signal-safe system calls
+            unsafe {
+                cmd.pre_exec(move || {
+                    setup.enter()?;
+                    libc::_exit(0);
+                });
+            }
+            let status = cmd.spawn()?.wait()?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(std::io::Error::other(format!("sandbox probe exited with {}", status)))
+            }
+        }
+
+        pub fn apply(&self, cmd: &mut Command) {
+            let setup = self.clone();
+            // SAFETY: `enter` only performs async-signal-safe system calls
+            unsafe {
+                cmd.pre_exec(move || setup.enter());
+            }
+        }
+
+        /// Create the namespaces and mounts. Runs in the forked child.
+        fn enter(&self) -> std::io::Result<()> {
+            unsafe {
+                check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS))?;
+
+                // setgroups must be denied before an unprivileged process may write gid_map
+                write_file(c"/proc/self/setgroups", c"deny").or_else(|e| {
+                    if e.raw_os_error() == Some(libc::ENOENT) { Ok(()) } else { Err(e) }
+                })?;
+                write_file(c"/proc/self/uid_map", &self.uid_map)?;
+                write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+                // Keep our mount changes from propagating back to the host
+                mount(None, c"/", None, libc::MS_REC | libc::MS_PRIVATE, None)?;
+
+                // The clean directory becomes its own mount so it stays writable
+                mount(
+                    Some(&self.writable_dir),
+                    &self.writable_dir,
+                    None,
+                    libc::MS_BIND | libc::MS_REC,
+                    None,
+                )?;
+
+                // ...apart from .c2rust and .git, which are bound back over it read-only
+                for (dir, flags) in &self.protected_dirs {
+                    mount(Some(dir), dir, None, libc::MS_BIND | libc::MS_REC, None)?;
+                    mount(None, dir, None, libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | flags, None)?;
+                }
+
+                for (mount_point, flags) in &self.readonly_mounts {
+                    let result = mount(
+                        None,
+                        mount_point,
+                        None,
+                        libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | flags,
+                        None,
+                    );
+                    match result {
+                        // Mount points hidden by other mounts cannot be reached and need no change
+                        Err(e) if e.raw_os_error() == Some(libc::ENOENT) => {}
+                        other => other?,
+                    }
+                }
+
+                // Step into the writable mount before /tmp is covered by the tmpfs
+                check(libc::chdir(self.writable_dir.as_ptr()))?;
+                mount(
+                    Some(c"tmpfs"),
+                    c"/tmp",
+                    Some(c"tmpfs"),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                    Some(c"mode=1777"),
+                )?;
+
+                if let Some(parents) = &self.tmp_parents {
+                    // The clean directory lives below /tmp: recreate its path in the
+                    // tmpfs and bind the (now hidden) directory back from our cwd
+                    for parent in parents {
+                        if libc::mkdir(parent.as_ptr(), 0o755) == -1
+                            && *libc::__errno_location() != libc::EEXIST
+                        {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                    if libc::mkdir(self.writable_dir.as_ptr(), 0o755) == -1
+                        && *libc::__errno_location() != libc::EEXIST
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    mount(Some(c"."), &self.writable_dir, None, libc::MS_BIND | libc::MS_REC, None)?;
+                }
+
+                // Re-enter the directory so the child's cwd is the writable mount
+                check(libc::chdir(self.writable_dir.as_ptr()))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn check(rc: libc::c_int) -> std::io::Result<()> {
+        if rc == -1 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    unsafe fn mount(
+        source: Option<&std::ffi::CStr>,
+        target: &std::ffi::CStr,
+        fstype: Option<&std::ffi::CStr>,
+        flags: libc::c_ulong,
+        data: Option<&std::ffi::CStr>,
+    ) -> std::io::Result<()> {
+        let ptr = |s: Option<&std::ffi::CStr>| s.map_or(std::ptr::null(), |s| s.as_ptr());
+        check(libc::mount(
+            ptr(source),
+            target.as_ptr(),
+            ptr(fstype),
+            flags,
+            ptr(data) as *const libc::c_void,
+        ))
+    }
+
+    unsafe fn write_file(path: &std::ffi::CStr, content: &std::ffi::CStr) -> std::io::Result<()> {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let bytes = content.to_bytes();
+        let written = libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len());
+        let result = if written == bytes.len() as isize {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        };
+        libc::close(fd);
+        result
+    }
+
+    /// Mount flags that cannot be cleared from inside a user namespace
+    fn locked_flags(path: &CString) -> libc::c_ulong {
+        // SAFETY: statvfs is plain data filled in by the kernel
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } == -1 {
+            return 0;
+        }
+        let mut flags = 0;
+        for (st, ms) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        flags
+    }
+
+    /// All mount points of the current mount namespace, parents before children
+    fn mount_points() -> std::io::Result<Vec<PathBuf>> {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+        let mut points: Vec<PathBuf> = mountinfo
+            .lines()
+            .filter_map(|line| line.split(' ').nth(4))
+            .map(|field| PathBuf::from(unescape(field)))
+            .collect();
+        points.sort();
+        points.dedup();
+        Ok(points)
+    }
+
+    /// Decode the octal escapes (`\040` for space, ...) used in mountinfo
+    fn unescape(field: &str) -> String {
+        let bytes = field.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let octal = bytes.get(i + 1..i + 4).filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+            if let (b'\\', Some(digits)) = (bytes[i], octal) {
+                let value = digits.iter().fold(0u8, |acc, d| acc.wrapping_mul(8).wrapping_add(d - b'0'));
+                out.push(value);
+                i += 4;
+            } else {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    fn cstring(path: &Path) -> std::io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_unescape_mountinfo() {
+            assert_eq!(unescape("/mnt/with\\040space"), "/mnt/with space");
+            assert_eq!(unescape("/plain"), "/plain");
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sandbox_restricts_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        let build = project.join("build");
+        fs::create_dir(&build).unwrap();
+
+        let sandbox = Sandbox::new(&build).unwrap();
+        if let Err(e) = sandbox.probe() {
+            eprintln!("skipping: namespace sandbox unavailable: {}", e);
+            return;
+        }
+
+        let mut cmd = Command::new("sh");
+        // The TempDir lives below /tmp, so `../outside` lands in the private tmpfs
+        cmd.args(["-c", "touch inside ../outside && ! touch /usr/c2rust-sandbox-test 2>/dev/null"])
+            .current_dir(&build);
+        sandbox.apply(&mut cmd);
+        let status = cmd.status().unwrap();
+
+        assert!(status.success(), "sandboxed command failed: {}", status);
+        assert!(build.join("inside").exists());
+        assert!(!project.join("outside").exists());
+    }
+
+    #[test]
+    fn test_sandbox_keeps_c2rust_and_git_read_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        for dir in [".c2rust", ".git"] {
+            fs::create_dir(project.join(dir)).unwrap();
+            fs::write(project.join(dir).join("config.toml"), "").unwrap();
+        }
+
+        // The clean directory is the project root itself
+        let sandbox = Sandbox::new(project).unwrap();
+        if let Err(e) = sandbox.probe() {
+            eprintln!("skipping: namespace sandbox unavailable: {}", e);
+            return;
+        }
+
+        for script in ["rm -rf .c2rust", "rm -rf .git", "touch .c2rust/new"] {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", script]).current_dir(project).stderr(std::process::Stdio::null());
+            sandbox.apply(&mut cmd);
+            let status = cmd.status().unwrap();
+            assert!(!status.success(), "'{}' succeeded in the sandbox", script);
+        }
+        assert!(project.join(".c2rust/config.toml").exists());
+        assert!(project.join(".git/config.toml").exists());
+        assert!(!project.join(".c2rust/new").exists());
+
+        // The rest of the clean directory stays writable
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "touch built.o && rm built.o"]).current_dir(project);
+        sandbox.apply(&mut cmd);
+        assert!(cmd.status().unwrap().success());
+    }
+}
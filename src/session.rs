@@ -0,0 +1,571 @@
+use crate::config_helper::{self, ConfigChange, ConfigToolOptions};
+use crate::config_store::{self, ConfigBackend, ConfigEntry, ConfigStore};
+use crate::error::{Error, Result};
+use crate::executor::{self, ExecOptions, RunStats};
+use crate::history::{self, RunRecord};
+use crate::limits::ResourceLimits;
+use crate::runner::Runner;
+use crate::safety::SafetyPolicy;
+use crate::steps::{self, CleanStep};
+use crate::{build_dir, git_helper, project, tracer};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Configures a [`CleanSession`]
+///
+/// Everything is optional except the clean command: either [`command`](Self::command)
+/// or one or more [`step`](Self::step)s / a [`steps_file`](Self::steps_file).
+#[derive(Debug, Clone)]
+pub struct CleanSessionBuilder {
+    dir: Option<PathBuf>,
+    source_dir: Option<PathBuf>,
+    feature: Option<String>,
+    command: Vec<String>,
+    step_specs: Vec<String>,
+    steps_file: Option<PathBuf>,
+    exec_options: ExecOptions,
+    keep_going: bool,
+    trace_deletions: bool,
+    protected: Vec<PathBuf>,
+    skip_safety_check: bool,
+    save_config: bool,
+    config_backend: ConfigBackend,
+    config_tool: ConfigToolOptions,
+    explain_config_tool: bool,
+    runner: Option<Runner>,
+}
+
+impl Default for CleanSessionBuilder {
+    fn default() -> Self {
+        CleanSessionBuilder {
+            dir: None,
+            source_dir: None,
+            feature: None,
+            command: Vec::new(),
+            step_specs: Vec::new(),
+            steps_file: None,
+            exec_options: ExecOptions::default(),
+            keep_going: false,
+            trace_deletions: false,
+            protected: Vec::new(),
+            skip_safety_check: false,
+            save_config: true,
+            config_backend: ConfigBackend::default(),
+            config_tool: ConfigToolOptions::default(),
+            explain_config_tool: false,
+            runner: None,
+        }
+    }
+}
+
+impl CleanSessionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory the clean runs from (default: the current directory)
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Source directory of an out-of-tree build (default: read from
+    /// `CMakeCache.txt` or `meson-info` in the clean directory)
+    pub fn source_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.source_dir = Some(dir.into());
+        self
+    }
+
+    /// Feature the configuration is saved under (default: "default")
+    pub fn feature(mut self, feature: impl Into<String>) -> Self {
+        self.feature = Some(feature.into());
+        self
+    }
+
+    /// Clean command run in the clean directory
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add a clean step as "[DIR:]COMMAND", like `--step`
+    pub fn step(mut self, spec: impl Into<String>) -> Self {
+        self.step_specs.push(spec.into());
+        self
+    }
+
+    /// Read clean steps from a file, one "[DIR:]COMMAND" per line, like `--steps-file`
+    pub fn steps_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.steps_file = Some(path.into());
+        self
+    }
+
+    /// How the clean commands run; resource limits not set here fall back to
+    /// the saved `clean.limits`
+    pub fn exec_options(mut self, options: ExecOptions) -> Self {
+        self.exec_options = options;
+        self
+    }
+
+    /// Keep running the remaining steps after a step fails
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Record the deletions of the clean in `.c2rust/clean/runs/<run-id>.deletions.jsonl`
+    ///
+    /// Building the session fails if the commands also run in a PTY.
+    pub fn trace_deletions(mut self, trace: bool) -> Self {
+        self.trace_deletions = trace;
+        self
+    }
+
+    /// Additional path (relative to the project root) that clean commands must not remove
+    pub fn protect(mut self, path: impl Into<PathBuf>) -> Self {
+        self.protected.push(path.into());
+        self
+    }
+
+    /// Do not reject destructive commands that escape the project root
+    pub fn skip_safety_check(mut self, skip: bool) -> Self {
+        self.skip_safety_check = skip;
+        self
+    }
+
+    /// Whether [`CleanSession::run`] saves the clean configuration (default: true)
+    pub fn save_config(mut self, save: bool) -> Self {
+        self.save_config = save;
+        self
+    }
+
+    pub fn config_backend(mut self, backend: ConfigBackend) -> Self {
+        self.config_backend = backend;
+        self
+    }
+
+    /// Path of the c2rust-config binary (`C2RUST_CONFIG` takes precedence)
+    pub fn config_tool(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_tool.path = Some(path.into());
+        self
+    }
+
+    /// Time limit of a single c2rust-config call
+    pub fn config_timeout(mut self, timeout: Duration) -> Self {
+        self.config_tool.timeout = timeout;
+        self
+    }
+
+    /// Start the clean commands and c2rust-config through `runner` instead of
+    /// as real processes, e.g. a [`RecordingRunner`](crate::runner::RecordingRunner) in tests
+    pub fn runner(mut self, runner: Runner) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// Print where c2rust-config was looked for when the session is built
+    pub fn explain_config_tool(mut self, explain: bool) -> Self {
+        self.explain_config_tool = explain;
+        self
+    }
+
+    /// Find the project root, open the configuration store and resolve the clean steps
+    pub fn build(mut self) -> Result<CleanSession> {
+        if let Some(runner) = self.runner.take() {
+            self.exec_options.runner = runner.clone();
+            self.config_tool.runner = runner;
+        }
+        let current_dir = std::env::current_dir()?;
+        let dir = match self.dir {
+            Some(dir) if dir.is_relative() => current_dir.join(dir),
+            Some(dir) => dir,
+            None => current_dir,
+        };
+        let feature = self.feature.unwrap_or_else(|| "default".to_string());
+        config_helper::validate_feature_name(&feature)?;
+
+        let project_root = project::locate_project_root(&dir, self.source_dir.as_deref())?;
+        if self.explain_config_tool {
+            let resolution = config_helper::resolve_c2rust_config(self.config_tool.path.as_deref(), Some(&project_root));
+            eprintln!("{}", resolution.explain());
+        }
+        let store = config_store::open(self.config_backend, &self.config_tool, &project_root)?;
+
+        let clean_dir_relative = build_dir::relative_dir(&dir, &project_root);
+        let single_command = self.step_specs.is_empty() && self.steps_file.is_none();
+        let clean_steps = if single_command {
+            if self.command.is_empty() {
+                return Err(Error::InvalidArgument("no clean command given".to_string()));
+            }
+            vec![CleanStep {
+                dir: clean_dir_relative.clone(),
+                command: self.command,
+            }]
+        } else {
+            if !self.command.is_empty() {
+                return Err(Error::InvalidArgument(
+                    "a clean command cannot be combined with clean steps".to_string(),
+                ));
+            }
+            let mut clean_steps = Vec::new();
+            if let Some(path) = &self.steps_file {
+                clean_steps.extend(steps::load_steps_file(path, &clean_dir_relative, &project_root)?);
+            }
+            for spec in &self.step_specs {
+                clean_steps.push(steps::parse_step(spec, &clean_dir_relative, &project_root)?);
+            }
+            clean_steps
+        };
+
+        // Out-of-tree build directories may be cleaned although they are outside the root
+        let step_dirs: Vec<PathBuf> = clean_steps.iter().map(|step| project_root.join(&step.dir)).collect();
+        let explicit: Vec<PathBuf> = self.source_dir.iter().map(|_| dir.clone()).collect();
+        let build_dirs = project::out_of_tree_build_dirs(&project_root, &step_dirs, &explicit);
+
+        if self.trace_deletions && self.exec_options.pty {
+            return Err(Error::InvalidArgument(
+                "deletion tracing cannot be combined with a PTY".to_string(),
+            ));
+        }
+
+        let record = RunRecord::new(&feature);
+        let requested_limits = self.exec_options.limits.clone();
+        let mut exec_options = self.exec_options;
+        exec_options.limits = requested_limits.clone().or(config_helper::load_limits(store.as_ref(), Some(&feature))?);
+        if self.trace_deletions {
+            exec_options.deletion_ledger = Some(tracer::ledger_path(&project_root, &record.run_id));
+        }
+
+        Ok(CleanSession {
+            dir,
+            project_root,
+            clean_dir_relative,
+            feature,
+            clean_steps,
+            single_command,
+            build_dirs,
+            store,
+            exec_options,
+            requested_limits,
+            keep_going: self.keep_going,
+            protected: self.protected,
+            skip_safety_check: self.skip_safety_check,
+            save_config: self.save_config,
+            record,
+        })
+    }
+}
+
+/// A clean run of one directory, from root detection to the commit of `.c2rust`
+///
+/// [`run`](Self::run) does everything in one go. The individual stages
+/// ([`check_safety`](Self::check_safety), [`execute`](Self::execute),
+/// [`save_config`](Self::save_config), [`commit`](Self::commit), ...) are
+/// public too, for callers that need to act in between.
+pub struct CleanSession {
+    dir: PathBuf,
+    project_root: PathBuf,
+    clean_dir_relative: String,
+    feature: String,
+    clean_steps: Vec<CleanStep>,
+    single_command: bool,
+    build_dirs: Vec<PathBuf>,
+    store: Box<dyn ConfigStore>,
+    exec_options: ExecOptions,
+    requested_limits: ResourceLimits,
+    keep_going: bool,
+    protected: Vec<PathBuf>,
+    skip_safety_check: bool,
+    save_config: bool,
+    record: RunRecord,
+}
+
+/// What [`CleanSession::run`] did
+#[derive(Debug, Clone)]
+pub struct CleanReport {
+    pub run_id: String,
+    /// Every step that ran, with its wall time and resource usage
+    pub steps: Vec<(CleanStep, RunStats)>,
+    pub config_saved: bool,
+    /// Where the run metadata was saved, if anywhere
+    pub record_path: Option<PathBuf>,
+    /// Warnings for the caller to show, such as a skipped safety check
+    pub warnings: Vec<String>,
+}
+
+impl CleanSession {
+    pub fn builder() -> CleanSessionBuilder {
+        CleanSessionBuilder::new()
+    }
+
+    /// The directory the clean runs from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
+    /// The clean directory relative to the project root, as saved in `clean.dir`
+    pub fn clean_dir_relative(&self) -> &str {
+        &self.clean_dir_relative
+    }
+
+    pub fn feature(&self) -> &str {
+        &self.feature
+    }
+
+    pub fn steps(&self) -> &[CleanStep] {
+        &self.clean_steps
+    }
+
+    /// Out-of-tree build directories the clean may touch besides the project root
+    pub fn build_dirs(&self) -> &[PathBuf] {
+        &self.build_dirs
+    }
+
+    pub fn store(&self) -> &dyn ConfigStore {
+        self.store.as_ref()
+    }
+
+    /// Options the steps run with, including the resolved resource limits
+    pub fn exec_options(&self) -> &ExecOptions {
+        &self.exec_options
+    }
+
+    /// Id of this run, used for the run metadata, deletion ledger and trash
+    pub fn run_id(&self) -> &str {
+        &self.record.run_id
+    }
+
+    /// The safety policy of the project root and its build directories,
+    /// regardless of [`CleanSessionBuilder::skip_safety_check`]
+    pub fn safety_policy(&self) -> SafetyPolicy {
+        self.build_dirs.iter().fold(
+            SafetyPolicy::new(&self.project_root, &self.protected),
+            |policy, dir| policy.allow_dir(dir),
+        )
+    }
+
+    /// Reject the session when any step is unsafe, before anything runs
+    ///
+    /// Returns a warning for the caller to show when the check was skipped.
+    pub fn check_safety(&self) -> Result<Option<String>> {
+        if self.skip_safety_check {
+            return Ok(Some("safety check disabled by --i-know-what-im-doing".to_string()));
+        }
+        let policy = self.safety_policy();
+        for step in &self.clean_steps {
+            policy.check(&self.project_root.join(&step.dir), &step.command)?;
+        }
+        Ok(None)
+    }
+
+    /// The clean configuration to save: `clean.dir`/`clean.cmd` for a single command,
+    /// the ordered `clean.steps` list otherwise
+    pub fn config_entries(&self) -> Result<Vec<ConfigEntry>> {
+        match self.clean_steps.as_slice() {
+            [step] if self.single_command => {
+                Ok(config_helper::single_command_entries(&step.dir, &steps::join_command(&step.command)))
+            }
+            _ => config_helper::steps_entries(&self.clean_steps),
+        }
+    }
+
+    /// How this run differs from the saved configuration
+    pub fn config_changes(&self) -> Result<Vec<ConfigChange>> {
+        config_helper::config_changes(self.store(), &self.config_entries()?, Some(&self.feature))
+    }
+
+    /// Run each step in order from its directory under the project root
+    ///
+    /// Stops at the first failing step unless `keep_going` is set, in which case
+    /// every step runs and the failures are reported together at the end.
+    pub fn execute(&self) -> Result<Vec<RunStats>> {
+        let mut failed = Vec::new();
+        let mut step_stats = Vec::new();
+
+        for (index, step) in self.clean_steps.iter().enumerate() {
+            if self.clean_steps.len() > 1 {
+                println!("Step {}/{}: {}", index + 1, self.clean_steps.len(), step.to_spec());
+            }
+
+            let step_dir = match step.dir.as_str() {
+                "." => self.project_root.clone(),
+                dir => self.project_root.join(dir),
+            };
+            let options = ExecOptions {
+                append_log: self.exec_options.append_log || index > 0,
+                ..self.exec_options.clone()
+            };
+
+            let result = if step_dir.is_dir() {
+                executor::execute_command(&step_dir, &step.command, &options)
+            } else {
+                Err(Error::InvalidStep(format!("directory '{}' does not exist", step_dir.display())))
+            };
+
+            match result {
+                Ok(stats) => step_stats.push(stats),
+                Err(e) if self.keep_going => {
+                    eprintln!("Warning: step {} failed: {}", index + 1, e);
+                    failed.push(step.to_spec());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(Error::StepsFailed(failed));
+        }
+
+        Ok(step_stats)
+    }
+
+    /// Save the clean configuration described by [`config_entries`](Self::config_entries)
+    pub fn save_config(&self) -> Result<()> {
+        match self.clean_steps.as_slice() {
+            [step] if self.single_command => config_helper::save_config(
+                self.store(),
+                &step.dir,
+                &steps::join_command(&step.command),
+                Some(&self.feature),
+            ),
+            _ => config_helper::save_steps_config(self.store(), &self.clean_steps, Some(&self.feature)),
+        }
+    }
+
+    /// Save the resource limits given to the builder (if any) as `clean.limits`
+    pub fn save_limits(&self) -> Result<()> {
+        if self.requested_limits.is_empty() {
+            return Ok(());
+        }
+        config_helper::save_limits(self.store(), &self.exec_options.limits, Some(&self.feature))
+    }
+
+    /// The run metadata for the stats returned by [`execute`](Self::execute)
+    pub fn record(&self, step_stats: Vec<RunStats>) -> RunRecord {
+        let mut record = self.record.clone();
+        for (step, stats) in self.clean_steps.iter().zip(step_stats) {
+            record.push_step(&step.dir, &steps::join_command(&step.command), stats);
+        }
+        record
+    }
+
+    /// Commit the changes in the `.c2rust` directory, if any
+    pub fn commit(&self) -> Result<()> {
+        git_helper::auto_commit_if_modified(&self.project_root)
+    }
+
+    /// Check, execute, save the configuration and run metadata, and commit
+    pub fn run(&self) -> Result<CleanReport> {
+        let warnings = self.check_safety()?.into_iter().collect();
+        let step_stats = self.execute()?;
+        if self.save_config {
+            self.save_config()?;
+            self.save_limits()?;
+        }
+        let record = self.record(step_stats.clone());
+        let record_path = history::save_record(&self.project_root, &record)?;
+        self.commit()?;
+
+        Ok(CleanReport {
+            run_id: record.run_id,
+            steps: self.clean_steps.iter().cloned().zip(step_stats).collect(),
+            config_saved: self.save_config,
+            record_path,
+            warnings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn native_session(root: &Path) -> CleanSessionBuilder {
+        CleanSession::builder().dir(root.join("build")).config_backend(ConfigBackend::Native)
+    }
+
+    #[test]
+    fn test_session_run_saves_and_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join(".c2rust")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+
+        let session = native_session(&root).feature("lib").command(["true"]).build().unwrap();
+        assert_eq!(session.project_root(), root);
+        assert_eq!(session.clean_dir_relative(), "build");
+        assert_eq!(session.config_changes().unwrap(), Vec::new());
+
+        let report = session.run().unwrap();
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.steps[0].0.command, vec!["true"]);
+        assert!(report.config_saved);
+        assert!(report.record_path.unwrap().exists());
+        assert_eq!(session.store().get("clean.dir", Some("lib")).unwrap().as_deref(), Some("build"));
+        assert_eq!(session.store().get("clean.cmd", Some("lib")).unwrap().as_deref(), Some("true"));
+
+        // A different command now shows up as a change to the saved configuration
+        let changed = native_session(&root).feature("lib").command(["false"]).build().unwrap();
+        assert_eq!(changed.config_changes().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_session_saves_quoted_command_and_reports_skipped_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join(".c2rust")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+
+        let command = ["sh", "-c", "true && true"];
+        let session = native_session(&root)
+            .command(command)
+            .skip_safety_check(true)
+            .build()
+            .unwrap();
+        let report = session.run().unwrap();
+        assert_eq!(report.warnings, vec!["safety check disabled by --i-know-what-im-doing"]);
+
+        // The saved command splits back into the same arguments
+        let saved = session.store().get("clean.cmd", None).unwrap().unwrap();
+        assert_eq!(saved, "sh -c 'true && true'");
+        assert_eq!(steps::split_command(&saved).unwrap(), command);
+        assert!(session.config_changes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_session_rejects_unsafe_and_invalid_input() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join(".c2rust")).unwrap();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+
+        let session = native_session(&root).command(["rm", "-rf", "/"]).build().unwrap();
+        assert!(matches!(session.check_safety(), Err(Error::UnsafeCommand(_))));
+        assert!(matches!(session.run(), Err(Error::UnsafeCommand(_))));
+        assert_eq!(session.store().get("clean.cmd", None).unwrap(), None);
+
+        assert!(matches!(native_session(&root).build(), Err(Error::InvalidArgument(_))));
+        assert!(matches!(
+            native_session(&root).command(["true"]).step("build:true").build(),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            native_session(&root).feature("a b").command(["true"]).build(),
+            Err(Error::InvalidArgument(_))
+        ));
+        let pty = ExecOptions { pty: true, ..Default::default() };
+        assert!(matches!(
+            native_session(&root).command(["true"]).exec_options(pty).trace_deletions(true).build(),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
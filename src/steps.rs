@@ -0,0 +1,272 @@
+use crate::build_dir;
+use crate::error::{Error, Result};
+use std::path::{Component, Path};
+
+/// A single step of a multi-step clean
+#[derive(Debug, Clone, PartialEq)]
+pub struct CleanStep {
+    /// Working directory relative to the project root
+    pub dir: String,
+    /// Command and its arguments
+    pub command: Vec<String>,
+}
+
+impl CleanStep {
+    /// Render the step in the same `DIR:COMMAND` form accepted by `--step`
+    pub fn to_spec(&self) -> String {
+        format!("{}:{}", self.dir, join_command(&self.command))
+    }
+}
+
+/// Parse a step specification of the form `[DIR:]COMMAND`
+///
+/// `DIR` is relative to the project root and is only recognized when the text
+/// before the first `:` contains no whitespace, so commands such as
+/// `rm -rf a:b` are left intact. Steps without a directory run in `default_dir`.
+///
+/// An absolute `DIR` or one that leaves the project root through `..` is
+/// rejected, unless it is a CMake or Meson build directory of the project.
+pub fn parse_step(spec: &str, default_dir: &str, project_root: &Path) -> Result<CleanStep> {
+    let spec = spec.trim();
+    let (dir, command) = match spec.split_once(':') {
+        Some((dir, rest)) if !dir.is_empty() && !dir.contains(char::is_whitespace) => {
+            check_step_dir(dir, project_root)?;
+            (dir.to_string(), rest)
+        }
+        _ => (default_dir.to_string(), spec),
+    };
+
+    let command = split_command(command)?;
+    if command.is_empty() {
+        return Err(Error::InvalidStep(format!("step '{}' has no command", spec)));
+    }
+
+    Ok(CleanStep { dir, command })
+}
+
+/// Reject a step directory outside `project_root` that is not one of its build directories
+fn check_step_dir(dir: &str, project_root: &Path) -> Result<()> {
+    let mut depth = 0usize;
+    let mut escapes = false;
+    for component in Path::new(dir).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => escapes = true,
+        }
+    }
+    let build_dir = || build_dir::detect(&project_root.join(dir)).is_some_and(|info| info.belongs_to(project_root));
+    if escapes && !build_dir() {
+        return Err(Error::InvalidStep(format!(
+            "step directory '{}' is outside the project root and is not a CMake or Meson build directory of it",
+            dir
+        )));
+    }
+    Ok(())
+}
+
+/// Load steps from a file, one `[DIR:]COMMAND` per line
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load_steps_file(path: &Path, default_dir: &str, project_root: &Path) -> Result<Vec<CleanStep>> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        Error::InvalidStep(format!("failed to read steps file {}: {}", path.display(), e))
+    })?;
+
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| parse_step(line, default_dir, project_root))
+        .collect()
+}
+
+/// Split a command line into arguments, honouring single/double quotes and backslash escapes
+pub fn split_command(input: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => {
+                            return Err(Error::InvalidStep(format!(
+                                "unterminated single quote in '{}'",
+                                input
+                            )))
+                        }
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => current.push('\\'),
+                        },
+                        Some(c) => current.push(c),
+                        None => {
+                            return Err(Error::InvalidStep(format!(
+                                "unterminated double quote in '{}'",
+                                input
+                            )))
+                        }
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    current.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+/// Split shell tokens on `;`, `&&`, `||` and `|`
+pub fn split_simple_commands(tokens: &[String]) -> Vec<Vec<String>> {
+    let mut result = vec![Vec::new()];
+    for token in tokens {
+        let (word, ends_command) = match token.strip_suffix(';') {
+            Some(word) => (word, true),
+            None => (token.as_str(), false),
+        };
+        if matches!(word, "&&" | "||" | "|") {
+            result.push(Vec::new());
+            continue;
+        }
+        if !word.is_empty() {
+            result.last_mut().unwrap().push(word.to_string());
+        }
+        if ends_command {
+            result.push(Vec::new());
+        }
+    }
+    result.retain(|c| !c.is_empty());
+    result
+}
+
+/// Join arguments back into a command line, quoting those that need it
+pub fn join_command(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let needs_quotes = arg.is_empty()
+                || arg
+                    .chars()
+                    .any(|c| c.is_whitespace() || matches!(c, '\'' | '"' | '\\' | '$' | '`'));
+            if needs_quotes {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step_with_dir() {
+        let step = parse_step("tests:make clean", ".", Path::new("/p")).unwrap();
+        assert_eq!(step.dir, "tests");
+        assert_eq!(step.command, vec!["make", "clean"]);
+    }
+
+    #[test]
+    fn test_parse_step_without_dir() {
+        let step = parse_step("rm -rf a:b", "build", Path::new("/p")).unwrap();
+        assert_eq!(step.dir, "build");
+        assert_eq!(step.command, vec!["rm", "-rf", "a:b"]);
+    }
+
+    #[test]
+    fn test_parse_step_empty_command() {
+        assert!(matches!(parse_step("tests:", ".", Path::new("/p")), Err(Error::InvalidStep(_))));
+    }
+
+    #[test]
+    fn test_parse_step_rejects_dirs_outside_root() {
+        let root = Path::new("/nonexistent/project");
+        assert!(matches!(parse_step("/etc:make clean", ".", root), Err(Error::InvalidStep(_))));
+        assert!(matches!(parse_step("../../x:rm -rf .", ".", root), Err(Error::InvalidStep(_))));
+        assert!(matches!(parse_step("a/../..:make clean", ".", root), Err(Error::InvalidStep(_))));
+        assert_eq!(parse_step("a/../b:make clean", ".", root).unwrap().dir, "a/../b");
+        // A step without DIR keeps the default directory, wherever the clean was started
+        assert_eq!(parse_step("make clean", "../build", root).unwrap().dir, "../build");
+    }
+
+    #[test]
+    fn test_parse_step_allows_out_of_tree_build_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("src");
+        let build = temp_dir.path().join("build");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&build).unwrap();
+        std::fs::write(
+            build.join("CMakeCache.txt"),
+            format!("CMAKE_HOME_DIRECTORY:INTERNAL={}\n", root.display()),
+        )
+        .unwrap();
+        assert_eq!(parse_step("../build:make clean", ".", &root).unwrap().dir, "../build");
+        assert!(parse_step("../other:make clean", ".", &root).is_err());
+    }
+
+    #[test]
+    fn test_split_command_quotes() {
+        let args = split_command(r#"find . -name "*.o" -o -name 'a b' \x"#).unwrap();
+        assert_eq!(args, vec!["find", ".", "-name", "*.o", "-o", "-name", "a b", "x"]);
+        assert!(split_command("echo 'oops").is_err());
+    }
+
+    #[test]
+    fn test_join_command_round_trip() {
+        let args = vec!["rm".to_string(), "-rf".to_string(), "a b".to_string(), "it's".to_string()];
+        assert_eq!(split_command(&join_command(&args)).unwrap(), args);
+    }
+
+    #[test]
+    fn test_load_steps_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("steps.txt");
+        std::fs::write(&path, "# clean everything\nmake clean\n\ntests:make clean\n").unwrap();
+        let steps = load_steps_file(&path, ".", temp_dir.path()).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].dir, ".");
+        assert_eq!(steps[1].to_spec(), "tests:make clean");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::runner::{CommandRunner, CommandSpec, Output, OutputSink, OutputStream};
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The outcome of a helper process run by [`run_with_timeout`]
+///
+/// Its `Display` form is a full diagnostic: the invocation, how it ended,
+/// and everything it printed on stdout and stderr.
+#[derive(Debug)]
+pub struct ToolRun {
+    /// The command line, quoted like a shell would need it
+    pub invocation: String,
+    pub dir: Option<PathBuf>,
+    /// `None` when the process was killed after `timeout`
+    pub status: Option<ExitStatus>,
+    pub timeout: Option<Duration>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ToolRun {
+    pub fn success(&self) -> bool {
+        self.status.is_some_and(|status| status.success())
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.status.is_none()
+    }
+
+    pub fn stdout_text(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+}
+
+impl std::fmt::Display for ToolRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`", self.invocation)?;
+        if let Some(dir) = &self.dir {
+            write!(f, " in {}", dir.display())?;
+        }
+        match (self.status, self.timeout) {
+            (None, Some(timeout)) => write!(f, " timed out after {}s and was killed", timeout.as_secs_f64())?,
+            (None, None) => write!(f, " was killed")?,
+            (Some(status), _) => match status.code() {
+                Some(code) => write!(f, " exited with code {}", code)?,
+                None => write!(f, " was terminated by a signal")?,
+            },
+        }
+        for (name, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            let text = String::from_utf8_lossy(output);
+            let text = text.trim_end();
+            if text.is_empty() {
+                write!(f, "\n  {}: (empty)", name)?;
+            } else {
+                write!(f, "\n  {}:", name)?;
+                for line in text.lines() {
+                    write!(f, "\n    {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run `command` to completion with stdin closed, capturing stdout and stderr
+///
+/// When `timeout` elapses first, the process and everything it started
+/// are killed and the returned [`ToolRun`] has no exit status. Fails only
+/// when the process cannot be started.
+pub fn run_with_timeout(runner: &dyn CommandRunner, command: CommandSpec, timeout: Option<Duration>) -> std::io::Result<ToolRun> {
+    let command = CommandSpec {
+        inherit_stdin: false,
+        process_group: true,
+        ..command
+    };
+    let output = Arc::new(Mutex::new((Vec::new(), Vec::new())));
+    let sink: OutputSink = {
+        let output = Arc::clone(&output);
+        Arc::new(move |stream, data: &[u8]| {
+            let mut output = output.lock().unwrap();
+            match stream {
+                OutputStream::Stdout => output.0.extend_from_slice(data),
+                OutputStream::Stderr => output.1.extend_from_slice(data),
+            }
+            Ok(())
+        })
+    };
+
+    let finished = runner.spawn(&command, Output::Stream(sink))?.wait(timeout)?;
+    let (stdout, stderr) = std::mem::take(&mut *output.lock().unwrap());
+    Ok(ToolRun {
+        invocation: command.to_string(),
+        dir: command.dir,
+        status: finished.status,
+        timeout,
+        stdout,
+        stderr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ProcessRunner;
+    use std::time::Instant;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_timeout_reports_everything() {
+        let run = run_with_timeout(
+            &ProcessRunner,
+            CommandSpec::new(["sh", "-c", "echo out; echo err >&2; exit 3"]),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        assert!(!run.success());
+        assert_eq!(run.stdout_text(), "out\n");
+        let message = run.to_string();
+        assert!(message.starts_with("`sh -c 'echo out; echo err >&2; exit 3'` exited with code 3"), "{}", message);
+        assert!(message.contains("stdout:\n    out"), "{}", message);
+        assert!(message.contains("stderr:\n    err"), "{}", message);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_timeout_kills_hung_process() {
+        let started = Instant::now();
+        let run = run_with_timeout(
+            &ProcessRunner,
+            CommandSpec::new(["sh", "-c", "echo started; sleep 30"]),
+            Some(Duration::from_millis(200)),
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(run.timed_out());
+        assert_eq!(run.stdout_text(), "started\n");
+        assert!(run.to_string().contains("timed out after 0.2s and was killed"), "{}", run);
+    }
+}
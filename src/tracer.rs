@@ -0,0 +1,601 @@
+//! Deletion tracing for clean commands.
+//!
+//! The clean command and all of its descendants are traced with `ptrace`, and
+//! every successful `unlink`, `rmdir` and `rename` system call is recorded
+//! together with the PID and command line that issued it. Unlike a before and
+//! after snapshot this also catches files that were created and removed within
+//! the same run.
+
+use crate::error::Result;
+use crate::history::runs_dir;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One file system entry removed (or renamed away) by a traced process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletionEvent {
+    pub pid: u32,
+    /// Command line of the process at the time of the call
+    pub command: String,
+    /// Kind of removal: `unlink`, `rmdir` or `rename`
+    pub op: String,
+    /// The system call that was made, e.g. `unlinkat`
+    pub syscall: String,
+    /// Absolute path of the removed entry (the source for renames)
+    pub path: String,
+    /// New path of a renamed entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// Ledger file for a run: `.c2rust/clean/runs/<run-id>.deletions.jsonl`
+pub fn ledger_path(project_root: &Path, run_id: &str) -> PathBuf {
+    runs_dir(project_root).join(format!("{}.deletions.jsonl", run_id))
+}
+
+/// Append `events` to the ledger at `path`, one JSON object per line
+///
+/// Like [`crate::history::save_record`], nothing is written when the ledger
+/// belongs in a `.c2rust` directory that does not exist, since c2rust-clean
+/// does not create that marker on its own. Returns whether the ledger was written.
+pub fn append_ledger(path: &Path, events: &[DeletionEvent]) -> Result<bool> {
+    let marker = path.ancestors().find(|dir| dir.file_name().is_some_and(|name| name == ".c2rust"));
+    if marker.is_some_and(|marker| !marker.is_dir()) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    for event in events {
+        let line = serde_json::to_string(event).map_err(std::io::Error::other)?;
+        writeln!(file, "{}", line)?;
+    }
+    Ok(true)
+}
+
+/// Whether deletion tracing is available on this platform
+pub fn supported() -> bool {
+    cfg!(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use linux::{trace_child, trace_me};
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+pub fn trace_me(_cmd: &mut std::process::Command) {}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod linux {
+    use super::DeletionEvent;
+    use crate::safety::normalize;
+    use std::collections::{HashMap, HashSet};
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::PathBuf;
+    use std::process::{Child, Command};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    /// Longest path we are willing to read from a tracee
+    const PATH_MAX: usize = 4096;
+
+    /// Make the child stop at `exec` so the parent can start tracing it
+    pub fn trace_me(cmd: &mut Command) {
+        // SAFETY: ptrace(PTRACE_TRACEME) is async-signal-safe
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// How a traced process tree ended
+    pub struct Traced {
+        /// Raw wait status of the traced child itself
+        pub status: libc::c_int,
+        pub rusage: libc::rusage,
+        /// Deletions made by the whole process tree
+        pub events: Vec<DeletionEvent>,
+        /// Whether the tree was killed because the deadline passed
+        pub timed_out: bool,
+    }
+
+    /// Trace `child` (spawned with [`trace_me`]) and its descendants until `child` exits
+    ///
+    /// Must be called from the thread that spawned `child`. Descendants still
+    /// running when `child` is reaped (daemons such as build servers) are
+    /// detached and left running; their later deletions are not recorded.
+    /// Once `deadline` passes, every traced process (and the process group led
+    /// by `child`, if any) is killed, and the tracing ends as soon as they are gone.
+    pub fn trace_child(child: &mut Child, deadline: Option<Instant>) -> std::io::Result<Traced> {
+        let root = child.id() as libc::pid_t;
+        let mut tracer = Tracer::default();
+        let mut root_exit = None;
+        // Dropped when the tracing ends, which releases the watchdog
+        let _watchdog = deadline.map(|deadline| watchdog(root, deadline, &tracer));
+
+        // The first stop is the SIGTRAP delivered when the child calls exec
+        let status = wait(root)?.0;
+        if libc::WIFSTOPPED(status) {
+            let options = libc::PTRACE_O_TRACESYSGOOD
+                | libc::PTRACE_O_TRACEFORK
+                | libc::PTRACE_O_TRACEVFORK
+                | libc::PTRACE_O_TRACECLONE
+                | libc::PTRACE_O_TRACEEXEC
+                | libc::PTRACE_O_EXITKILL;
+            // SAFETY: `root` is our stopped tracee
+            unsafe {
+                check(libc::ptrace(libc::PTRACE_SETOPTIONS, root, 0, options))?;
+            }
+            tracer.known.lock().unwrap().insert(root);
+            resume(root, 0)?;
+        } else {
+            return Ok(Traced {
+                status,
+                // SAFETY: rusage is plain data
+                rusage: unsafe { std::mem::zeroed() },
+                events: Vec::new(),
+                timed_out: false,
+            });
+        }
+
+        loop {
+            let (status, pid, rusage) = match wait(-1) {
+                Ok(waited) => waited,
+                Err(e) if e.raw_os_error() == Some(libc::ECHILD) => break,
+                Err(e) => return Err(e),
+            };
+
+            if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                tracer.forget(pid);
+                if pid == root {
+                    root_exit = Some((status, rusage));
+                    tracer.detach_all()?;
+                    break;
+                }
+                continue;
+            }
+            if !libc::WIFSTOPPED(status) {
+                continue;
+            }
+            if tracer.expired.load(Ordering::SeqCst) {
+                // Also catches processes forked while the watchdog was killing the others
+                // SAFETY: kill has no memory safety requirements
+                unsafe {
+                    libc::kill(pid, libc::SIGKILL);
+                }
+            }
+
+            let signal = libc::WSTOPSIG(status);
+            let event = status >> 16;
+            if signal == libc::SIGTRAP | 0x80 {
+                tracer.syscall_stop(pid);
+                resume(pid, 0)?;
+            } else if event != 0 {
+                if event == libc::PTRACE_EVENT_EXEC {
+                    tracer.commands.remove(&pid);
+                }
+                resume(pid, 0)?;
+            } else if signal == libc::SIGSTOP && tracer.known.lock().unwrap().insert(pid) {
+                // Initial stop of a newly traced child
+                resume(pid, 0)?;
+            } else {
+                // Ordinary signal: deliver it to the tracee
+                resume(pid, signal)?;
+            }
+        }
+
+        let (status, rusage) = root_exit.ok_or_else(|| {
+            std::io::Error::other("traced command disappeared without an exit status")
+        })?;
+        Ok(Traced {
+            status,
+            rusage,
+            events: tracer.events,
+            timed_out: tracer.expired.load(Ordering::SeqCst),
+        })
+    }
+
+    /// Kill the traced processes of `tracer` at `deadline`, unless the returned sender is dropped first
+    fn watchdog(root: libc::pid_t, deadline: Instant, tracer: &Tracer) -> mpsc::Sender<()> {
+        let (done, finished) = mpsc::channel::<()>();
+        let known = Arc::clone(&tracer.known);
+        let expired = Arc::clone(&tracer.expired);
+        std::thread::spawn(move || {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if finished.recv_timeout(timeout) != Err(mpsc::RecvTimeoutError::Timeout) {
+                return;
+            }
+            expired.store(true, Ordering::SeqCst);
+            // SAFETY: kill and getpgid have no memory safety requirements
+            unsafe {
+                if libc::getpgid(root) == root {
+                    libc::kill(-root, libc::SIGKILL);
+                }
+                for pid in known.lock().unwrap().iter() {
+                    libc::kill(*pid, libc::SIGKILL);
+                }
+            }
+        });
+        done
+    }
+
+    /// A removal syscall seen on entry, recorded if it succeeds
+    struct Pending {
+        op: &'static str,
+        syscall: &'static str,
+        path: String,
+        target: Option<String>,
+    }
+
+    #[derive(Default)]
+    struct Tracer {
+        /// Shared with the watchdog, which kills them when the deadline passes
+        known: Arc<Mutex<HashSet<libc::pid_t>>>,
+        expired: Arc<AtomicBool>,
+        /// Tracees currently inside a system call, with the call to record on exit
+        in_syscall: HashMap<libc::pid_t, Option<Pending>>,
+        commands: HashMap<libc::pid_t, String>,
+        events: Vec<DeletionEvent>,
+    }
+
+    impl Tracer {
+        /// Stop tracing every remaining tracee and let it run on untraced
+        ///
+        /// Each tracee is sent SIGSTOP so that it reaches a ptrace stop where it
+        /// can be detached. Children forked in the meantime report their initial
+        /// stop here as well and are detached the same way. Once the deadline
+        /// has passed, the tracees are killed instead.
+        fn detach_all(&mut self) -> std::io::Result<()> {
+            for pid in self.known.lock().unwrap().iter() {
+                // SAFETY: kill has no memory safety requirements
+                unsafe {
+                    libc::kill(*pid, libc::SIGSTOP);
+                }
+            }
+            loop {
+                let (status, pid) = match wait(-1) {
+                    Ok((status, pid, _)) => (status, pid),
+                    // Only our own tracees are waited for, so this means all are gone
+                    Err(e) if e.raw_os_error() == Some(libc::ECHILD) => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                if !libc::WIFSTOPPED(status) {
+                    self.forget(pid);
+                    continue;
+                }
+                if self.expired.load(Ordering::SeqCst) {
+                    // SAFETY: kill has no memory safety requirements
+                    unsafe {
+                        libc::kill(pid, libc::SIGKILL);
+                    }
+                    resume(pid, 0)?;
+                    continue;
+                }
+                // Our own SIGSTOP, or the initial stop of a new child, is swallowed
+                // by detaching without a signal. Any other stop leaves a pending
+                // SIGSTOP behind, which SIGCONT discards.
+                let signal = libc::WSTOPSIG(status);
+                let swallowed = signal == libc::SIGSTOP && status >> 16 == 0;
+                // SAFETY: `pid` is a stopped tracee
+                unsafe {
+                    libc::ptrace(libc::PTRACE_DETACH, pid, 0, 0);
+                }
+                if !swallowed {
+                    // SAFETY: kill has no memory safety requirements
+                    unsafe {
+                        libc::kill(pid, libc::SIGCONT);
+                    }
+                }
+                self.forget(pid);
+            }
+        }
+
+        fn forget(&mut self, pid: libc::pid_t) {
+            self.known.lock().unwrap().remove(&pid);
+            self.in_syscall.remove(&pid);
+            self.commands.remove(&pid);
+        }
+
+        fn syscall_stop(&mut self, pid: libc::pid_t) {
+            let regs = Regs::read(pid).ok();
+            match self.in_syscall.remove(&pid) {
+                None => {
+                    let pending = regs.and_then(|regs| decode(pid, &regs));
+                    self.in_syscall.insert(pid, pending);
+                }
+                Some(Some(pending)) if regs.is_some_and(|regs| regs.ret == 0) => {
+                    let command = self
+                        .commands
+                        .entry(pid)
+                        .or_insert_with(|| command_line(pid))
+                        .clone();
+                    self.events.push(DeletionEvent {
+                        pid: pid as u32,
+                        command,
+                        op: pending.op.to_string(),
+                        syscall: pending.syscall.to_string(),
+                        path: pending.path,
+                        target: pending.target,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Work out which removal (if any) a syscall entry represents
+    fn decode(pid: libc::pid_t, regs: &Regs) -> Option<Pending> {
+        let [a0, a1, a2, a3] = regs.args;
+        let at = |dirfd: u64, ptr: u64| resolve(pid, dirfd as i32, ptr);
+        let cwd = libc::AT_FDCWD as u64;
+
+        let (op, syscall, path, target) = match regs.nr {
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_unlink => ("unlink", "unlink", at(cwd, a0)?, None),
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_rmdir => ("rmdir", "rmdir", at(cwd, a0)?, None),
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_rename => ("rename", "rename", at(cwd, a0)?, Some(at(cwd, a1)?)),
+            libc::SYS_unlinkat => {
+                let op = if a2 as i32 & libc::AT_REMOVEDIR != 0 { "rmdir" } else { "unlink" };
+                (op, "unlinkat", at(a0, a1)?, None)
+            }
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_renameat => ("rename", "renameat", at(a0, a1)?, Some(at(a2, a3)?)),
+            libc::SYS_renameat2 => ("rename", "renameat2", at(a0, a1)?, Some(at(a2, a3)?)),
+            _ => return None,
+        };
+        Some(Pending { op, syscall, path, target })
+    }
+
+    /// Turn a path argument of `pid` into an absolute path
+    fn resolve(pid: libc::pid_t, dirfd: i32, ptr: u64) -> Option<String> {
+        let path = PathBuf::from(read_string(pid, ptr)?);
+        let base = if path.is_absolute() {
+            PathBuf::new()
+        } else if dirfd == libc::AT_FDCWD {
+            std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()?
+        } else {
+            std::fs::read_link(format!("/proc/{}/fd/{}", pid, dirfd)).ok()?
+        };
+        Some(normalize(&base.join(path)).to_string_lossy().into_owned())
+    }
+
+    /// Read a NUL terminated string from the tracee's memory
+    fn read_string(pid: libc::pid_t, addr: u64) -> Option<String> {
+        let mem = std::fs::File::open(format!("/proc/{}/mem", pid)).ok()?;
+        let mut result = Vec::new();
+        let mut offset = addr;
+        while result.len() < PATH_MAX {
+            // Never read across a page boundary in one go: the next page may be unmapped
+            let mut buf = [0u8; 256];
+            let to_page_end = 4096 - (offset % 4096) as usize;
+            let len = buf.len().min(to_page_end);
+            let n = mem.read_at(&mut buf[..len], offset).ok()?;
+            if n == 0 {
+                return None;
+            }
+            if let Some(end) = buf[..n].iter().position(|&b| b == 0) {
+                result.extend_from_slice(&buf[..end]);
+                return Some(String::from_utf8_lossy(&result).into_owned());
+            }
+            result.extend_from_slice(&buf[..n]);
+            offset += n as u64;
+        }
+        None
+    }
+
+    fn command_line(pid: libc::pid_t) -> String {
+        std::fs::read(format!("/proc/{}/cmdline", pid))
+            .map(|raw| {
+                raw.split(|&b| b == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default()
+    }
+
+    /// Syscall number, first four arguments and return value of a stopped tracee
+    struct Regs {
+        nr: libc::c_long,
+        args: [u64; 4],
+        ret: i64,
+    }
+
+    impl Regs {
+        #[cfg(target_arch = "x86_64")]
+        fn read(pid: libc::pid_t) -> std::io::Result<Regs> {
+            // SAFETY: user_regs_struct is plain data filled in by the kernel
+            let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+            unsafe {
+                check(libc::ptrace(libc::PTRACE_GETREGS, pid, 0, &mut regs))?;
+            }
+            Ok(Regs {
+                nr: regs.orig_rax as libc::c_long,
+                args: [regs.rdi, regs.rsi, regs.rdx, regs.r10],
+                ret: regs.rax as i64,
+            })
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        fn read(pid: libc::pid_t) -> std::io::Result<Regs> {
+            // SAFETY: user_regs_struct is plain data filled in by the kernel
+            let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+            let mut iov = libc::iovec {
+                iov_base: &mut regs as *mut _ as *mut libc::c_void,
+                iov_len: std::mem::size_of::<libc::user_regs_struct>(),
+            };
+            unsafe {
+                check(libc::ptrace(libc::PTRACE_GETREGSET, pid, libc::NT_PRSTATUS, &mut iov))?;
+            }
+            Ok(Regs {
+                nr: regs.regs[8] as libc::c_long,
+                args: [regs.regs[0], regs.regs[1], regs.regs[2], regs.regs[3]],
+                ret: regs.regs[0] as i64,
+            })
+        }
+    }
+
+    fn wait(pid: libc::pid_t) -> std::io::Result<(libc::c_int, libc::pid_t, libc::rusage)> {
+        let mut status = 0;
+        // SAFETY: rusage is plain data filled in by the kernel
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: pointers are valid for the duration of the call
+            // __WNOTHREAD: never reap children that other threads are waiting for
+            let rc = unsafe { libc::wait4(pid, &mut status, libc::__WALL | libc::__WNOTHREAD, &mut rusage) };
+            if rc > 0 {
+                return Ok((status, rc, rusage));
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    fn resume(pid: libc::pid_t, signal: libc::c_int) -> std::io::Result<()> {
+        // SAFETY: `pid` is a stopped tracee
+        let rc = unsafe { libc::ptrace(libc::PTRACE_SYSCALL, pid, 0, signal) };
+        match rc {
+            // The tracee may have been killed in the meantime
+            -1 if std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            rc => check(rc),
+        }
+    }
+
+    fn check(rc: libc::c_long) -> std::io::Result<()> {
+        if rc == -1 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_ledger() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+        let path = ledger_path(temp_dir.path(), "1-2");
+        let event = DeletionEvent {
+            pid: 42,
+            command: "rm -f a.o".to_string(),
+            op: "unlink".to_string(),
+            syscall: "unlinkat".to_string(),
+            path: "/project/a.o".to_string(),
+            target: None,
+        };
+        append_ledger(&path, std::slice::from_ref(&event)).unwrap();
+        append_ledger(&path, std::slice::from_ref(&event)).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let loaded: Vec<DeletionEvent> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(loaded, vec![event.clone(), event]);
+    }
+
+    #[test]
+    fn test_append_ledger_needs_c2rust_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = ledger_path(temp_dir.path(), "1-2");
+        assert!(!append_ledger(&path, &[]).unwrap());
+        assert!(!temp_dir.path().join(".c2rust").exists());
+
+        // Ledgers outside of a `.c2rust` directory are always written
+        let path = temp_dir.path().join("deletions.jsonl");
+        assert!(append_ledger(&path, &[]).unwrap());
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_trace_records_deletions() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap();
+        fs::write(dir.join("old.o"), "").unwrap();
+        fs::create_dir(dir.join("obj")).unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "touch tmp.o && rm tmp.o old.o && rmdir obj && touch a && mv a b"])
+            .current_dir(&dir);
+        trace_me(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let traced = trace_child(&mut child, None).unwrap();
+        let (status, events) = (traced.status, traced.events);
+
+        assert!(!traced.timed_out);
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+        let removed: Vec<(&str, String)> = events
+            .iter()
+            .map(|e| (e.op.as_str(), e.path.clone()))
+            .collect();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        assert!(removed.contains(&("unlink", path("tmp.o"))), "{:?}", events);
+        assert!(removed.contains(&("unlink", path("old.o"))), "{:?}", events);
+        assert!(removed.contains(&("rmdir", path("obj"))), "{:?}", events);
+
+        let rename = events.iter().find(|e| e.op == "rename").unwrap();
+        assert_eq!(rename.path, path("a"));
+        assert_eq!(rename.target.as_deref(), Some(path("b").as_str()));
+        assert!(events.iter().any(|e| e.command.starts_with("rm ")));
+    }
+
+    #[test]
+    fn test_trace_child_detaches_daemons() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $! > daemon.pid; rm -f first"]).current_dir(temp_dir.path());
+        trace_me(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+
+        let started = Instant::now();
+        let traced = trace_child(&mut child, None).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!traced.timed_out);
+        assert!(libc::WIFEXITED(traced.status) && libc::WEXITSTATUS(traced.status) == 0);
+
+        // The daemon keeps running untraced and is not left stopped
+        let pid: libc::pid_t = fs::read_to_string(temp_dir.path().join("daemon.pid")).unwrap().trim().parse().unwrap();
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+        assert!(status.lines().any(|line| line == "TracerPid:\t0"), "{}", status);
+        assert!(!status.lines().any(|line| line.starts_with("State:\tT")), "{}", status);
+        // SAFETY: kill has no memory safety requirements
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+
+    #[test]
+    fn test_trace_child_kills_tree_at_deadline() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = Command::new("sh");
+        // The subshell keeps running after the top-level shell is killed unless it is traced
+        cmd.args(["-c", "(sleep 30; rm -f never) & rm -f first; wait"]).current_dir(temp_dir.path());
+        trace_me(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+
+        let started = Instant::now();
+        let traced = trace_child(&mut child, Some(Instant::now() + Duration::from_millis(300))).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(traced.timed_out);
+        assert!(libc::WIFSIGNALED(traced.status));
+        assert!(traced.events.iter().all(|e| !e.path.ends_with("never")));
+    }
+}
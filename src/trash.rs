@@ -0,0 +1,413 @@
+use crate::error::{Error, Result};
+use crate::project::SKIPPED_DIRS;
+use crate::safety::normalize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Run sub-directory for files outside the project root, such as those of an
+/// out-of-tree build directory; they are staged under their absolute path
+const OUTSIDE_DIR: &str = ".outside";
+
+/// Directory holding one sub-directory per trashed run
+pub fn trash_dir(project_root: &Path) -> PathBuf {
+    project_root.join(".c2rust").join("trash")
+}
+
+/// Hard-linked copy of the clean directories taken before the clean runs
+///
+/// Every regular file below the snapshotted directories is linked (or copied
+/// when linking is impossible) to `.c2rust/trash/<run-id>/<path relative to
+/// the project root>`, or `.c2rust/trash/<run-id>/.outside/<absolute path>`
+/// for files outside the project root. After the clean, [`TrashSnapshot::finish`] keeps only
+/// the entries whose original file is gone, so the trash holds exactly what
+/// the clean removed.
+#[derive(Debug)]
+pub struct TrashSnapshot {
+    project_root: PathBuf,
+    run_dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl TrashSnapshot {
+    /// Snapshot every file below `dirs` (absolute paths)
+    pub fn take(project_root: &Path, run_id: &str, dirs: &[PathBuf]) -> Result<Self> {
+        let mut snapshot = Self::empty(project_root, run_id)?;
+        for dir in dirs {
+            snapshot.add_dir(&normalize(dir))?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Snapshot just `files` (absolute paths)
+    pub fn take_files(project_root: &Path, run_id: &str, files: &[PathBuf]) -> Result<Self> {
+        let mut snapshot = Self::empty(project_root, run_id)?;
+        for file in files {
+            snapshot.add_file(&normalize(file))?;
+        }
+        Ok(snapshot)
+    }
+
+    fn empty(project_root: &Path, run_id: &str) -> Result<Self> {
+        let trash = trash_dir(project_root);
+        std::fs::create_dir_all(&trash).map_err(|e| trash_error("create", &trash, e))?;
+        // Keep trashed artifacts out of the auto-committed .c2rust repository
+        let gitignore = trash.join(".gitignore");
+        if !gitignore.exists() {
+            std::fs::write(&gitignore, "*\n").map_err(|e| trash_error("write", &gitignore, e))?;
+        }
+
+        Ok(TrashSnapshot {
+            project_root: normalize(project_root),
+            run_dir: trash.join(run_id),
+            files: Vec::new(),
+        })
+    }
+
+    fn add_dir(&mut self, dir: &Path) -> Result<()> {
+        let entries = std::fs::read_dir(dir).map_err(|e| trash_error("read", dir, e))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if !SKIPPED_DIRS.iter().any(|skip| entry.file_name() == *skip) {
+                    self.add_dir(&path)?;
+                }
+            } else if file_type.is_file() {
+                self.add_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        let relative = match path.strip_prefix(&self.project_root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) if path.is_absolute() => path.to_path_buf(),
+            Err(_) => {
+                eprintln!("Warning: not moving {} to the trash: not an absolute path", path.display());
+                return Ok(());
+            }
+        };
+        let staged = staged_path(&self.run_dir, &relative);
+        if staged.exists() {
+            // Already staged through an overlapping directory
+            return Ok(());
+        }
+        if let Some(parent) = staged.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| trash_error("create", parent, e))?;
+        }
+        if std::fs::hard_link(path, &staged).is_err() {
+            // Different filesystem or no hard link support: fall back to a copy
+            std::fs::copy(path, &staged).map_err(|e| trash_error("copy", path, e))?;
+        }
+        self.files.push(relative);
+        Ok(())
+    }
+
+    /// Keep the staged files whose originals were removed, drop the rest
+    ///
+    /// Returns the trashed paths relative to the project root (absolute for
+    /// files outside it). The run
+    /// directory is removed entirely when nothing was deleted.
+    pub fn finish(self) -> Result<Vec<PathBuf>> {
+        let mut trashed = Vec::new();
+        for relative in &self.files {
+            let original = self.project_root.join(relative);
+            if original.symlink_metadata().is_ok() {
+                std::fs::remove_file(staged_path(&self.run_dir, relative))?;
+            } else {
+                trashed.push(relative.clone());
+            }
+        }
+
+        if trashed.is_empty() {
+            if self.run_dir.exists() {
+                std::fs::remove_dir_all(&self.run_dir)
+                    .map_err(|e| trash_error("remove", &self.run_dir, e))?;
+            }
+        } else {
+            remove_empty_dirs(&self.run_dir)?;
+        }
+        Ok(trashed)
+    }
+}
+
+/// Summary of one run kept in the trash
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashEntry {
+    pub run_id: String,
+    pub files: usize,
+    pub bytes: u64,
+    /// Creation time derived from the run id (seconds since the Unix epoch)
+    pub created_at: Option<u64>,
+}
+
+/// List the runs currently kept in the trash, oldest first
+pub fn list(project_root: &Path) -> Result<Vec<TrashEntry>> {
+    let trash = trash_dir(project_root);
+    if !trash.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(&trash)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let run_id = entry.file_name().to_string_lossy().to_string();
+        let (files, bytes) = tree_size(&entry.path())?;
+        entries.push(TrashEntry {
+            created_at: run_id.split('-').next().and_then(|secs| secs.parse().ok()),
+            run_id,
+            files,
+            bytes,
+        });
+    }
+    entries.sort_by(|a, b| (a.created_at, &a.run_id).cmp(&(b.created_at, &b.run_id)));
+    Ok(entries)
+}
+
+/// Move the files of `run_id` back to their original locations
+///
+/// Files that exist again at their original location are left in the trash
+/// unless `force` is set. The run is removed from the trash once every file
+/// has been restored. Returns the restored paths relative to the project root
+/// (absolute for files outside it).
+pub fn restore(project_root: &Path, run_id: &str, force: bool) -> Result<Vec<PathBuf>> {
+    if run_id.is_empty() || run_id.contains(['/', '\\']) || run_id.starts_with('.') {
+        return Err(Error::TrashFailed(format!("invalid run id '{}'", run_id)));
+    }
+    let run_dir = trash_dir(project_root).join(run_id);
+    if !run_dir.is_dir() {
+        return Err(Error::TrashFailed(format!("no trashed run '{}' found", run_id)));
+    }
+
+    let mut files = Vec::new();
+    collect_files(&run_dir, &mut files)?;
+
+    let mut restored = Vec::new();
+    let mut skipped = Vec::new();
+    for staged in files {
+        let staged_relative = staged.strip_prefix(&run_dir).unwrap_or(&staged);
+        let relative = match staged_relative.strip_prefix(OUTSIDE_DIR) {
+            Ok(outside) => Path::new("/").join(outside),
+            Err(_) => staged_relative.to_path_buf(),
+        };
+        let target = project_root.join(&relative);
+        if target.symlink_metadata().is_ok() && !force {
+            skipped.push(relative.display().to_string());
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| trash_error("create", parent, e))?;
+        }
+        if std::fs::rename(&staged, &target).is_err() {
+            std::fs::copy(&staged, &target).map_err(|e| trash_error("restore", &target, e))?;
+            std::fs::remove_file(&staged)?;
+        }
+        restored.push(relative);
+    }
+
+    if !skipped.is_empty() {
+        remove_empty_dirs(&run_dir)?;
+        return Err(Error::TrashFailed(format!(
+            "{} file(s) already exist and were kept in the trash (use --force to overwrite): {}",
+            skipped.len(),
+            skipped.join(", ")
+        )));
+    }
+
+    std::fs::remove_dir_all(&run_dir).map_err(|e| trash_error("remove", &run_dir, e))?;
+    Ok(restored)
+}
+
+/// Remove trashed runs older than `max_age`, then the oldest runs until the
+/// trash is no larger than `max_bytes`. Returns the removed run ids.
+pub fn prune(project_root: &Path, max_age: Option<Duration>, max_bytes: Option<u64>) -> Result<Vec<String>> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut entries = list(project_root)?;
+    let mut removed = Vec::new();
+
+    if let Some(max_age) = max_age {
+        let cutoff = now.saturating_sub(max_age.as_secs());
+        entries.retain(|entry| {
+            let expired = entry.created_at.is_some_and(|created| created < cutoff);
+            if expired {
+                removed.push(entry.run_id.clone());
+            }
+            !expired
+        });
+    }
+
+    if let Some(max_bytes) = max_bytes {
+        let mut total: u64 = entries.iter().map(|e| e.bytes).sum();
+        // Entries are sorted oldest first
+        for entry in &entries {
+            if total <= max_bytes {
+                break;
+            }
+            total -= entry.bytes;
+            removed.push(entry.run_id.clone());
+        }
+    }
+
+    let trash = trash_dir(project_root);
+    for run_id in &removed {
+        let run_dir = trash.join(run_id);
+        std::fs::remove_dir_all(&run_dir).map_err(|e| trash_error("remove", &run_dir, e))?;
+    }
+    Ok(removed)
+}
+
+/// Where the file at `relative` (or an absolute path outside the project) is staged
+fn staged_path(run_dir: &Path, relative: &Path) -> PathBuf {
+    match relative.strip_prefix("/") {
+        Ok(outside) => run_dir.join(OUTSIDE_DIR).join(outside),
+        Err(_) => run_dir.join(relative),
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn tree_size(dir: &Path) -> Result<(usize, u64)> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    let bytes = files
+        .iter()
+        .filter_map(|f| f.symlink_metadata().ok())
+        .map(|m| m.len())
+        .sum();
+    Ok((files.len(), bytes))
+}
+
+/// Remove empty directories below (and including) `dir`
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+        }
+    }
+    if std::fs::read_dir(dir)?.next().is_none() {
+        std::fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+fn trash_error(action: &str, path: &Path, e: std::io::Error) -> Error {
+    Error::TrashFailed(format!("failed to {} {}: {}", action, path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_snapshot_keeps_only_deleted_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("build").join("obj")).unwrap();
+        fs::write(root.join("build").join("obj").join("a.o"), "a").unwrap();
+        fs::write(root.join("build").join("keep.txt"), "k").unwrap();
+
+        let snapshot = TrashSnapshot::take(root, "100-1", &[root.join("build")]).unwrap();
+        fs::remove_file(root.join("build").join("obj").join("a.o")).unwrap();
+        let trashed = snapshot.finish().unwrap();
+
+        assert_eq!(trashed, vec![PathBuf::from("build/obj/a.o")]);
+        let run_dir = trash_dir(root).join("100-1");
+        assert_eq!(fs::read_to_string(run_dir.join("build/obj/a.o")).unwrap(), "a");
+        assert!(!run_dir.join("build/keep.txt").exists());
+    }
+
+    #[test]
+    fn test_snapshot_without_deletions_leaves_no_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("file.txt"), "x").unwrap();
+
+        let snapshot = TrashSnapshot::take(root, "100-2", &[root.to_path_buf()]).unwrap();
+        assert!(snapshot.finish().unwrap().is_empty());
+        assert!(list(root).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore_moves_files_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("gen")).unwrap();
+        fs::write(root.join("gen").join("config.h"), "hand edited").unwrap();
+
+        let snapshot = TrashSnapshot::take(root, "100-3", &[root.join("gen")]).unwrap();
+        fs::remove_dir_all(root.join("gen")).unwrap();
+        snapshot.finish().unwrap();
+
+        let restored = restore(root, "100-3", false).unwrap();
+        assert_eq!(restored, vec![PathBuf::from("gen/config.h")]);
+        assert_eq!(fs::read_to_string(root.join("gen").join("config.h")).unwrap(), "hand edited");
+        assert!(!trash_dir(root).join("100-3").exists());
+        assert!(matches!(restore(root, "../x", false), Err(Error::TrashFailed(_))));
+    }
+
+    #[test]
+    fn test_out_of_tree_build_dir_is_staged_inside_the_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().canonicalize().unwrap();
+        let root = base.join("project");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(base.join("build")).unwrap();
+        fs::write(base.join("build").join("x.o"), "x").unwrap();
+
+        // `../build` as a step dir joins to a path with `..` in it
+        let snapshot = TrashSnapshot::take(&root, "100-5", &[root.join("..").join("build")]).unwrap();
+        fs::remove_file(base.join("build").join("x.o")).unwrap();
+        let trashed = snapshot.finish().unwrap();
+
+        assert_eq!(trashed, vec![base.join("build").join("x.o")]);
+        let runs: Vec<String> = list(&root).unwrap().into_iter().map(|e| e.run_id).collect();
+        assert_eq!(runs, vec!["100-5".to_string()]);
+        assert!(!trash_dir(&root).join("build").exists());
+
+        let restored = restore(&root, "100-5", false).unwrap();
+        assert_eq!(restored, vec![base.join("build").join("x.o")]);
+        assert_eq!(fs::read_to_string(base.join("build").join("x.o")).unwrap(), "x");
+    }
+
+    #[test]
+    fn test_prune_by_age_and_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        for (run_id, size) in [
+            (format!("{}-1", now - 30 * 86400), 10),
+            (format!("{}-2", now - 60), 100),
+            (format!("{}-3", now), 100),
+        ] {
+            let dir = trash_dir(root).join(run_id);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("f"), vec![0u8; size]).unwrap();
+        }
+
+        let removed = prune(root, Some(Duration::from_secs(7 * 86400)), Some(150)).unwrap();
+        assert_eq!(removed, vec![format!("{}-1", now - 30 * 86400), format!("{}-2", now - 60)]);
+        let remaining: Vec<String> = list(root).unwrap().into_iter().map(|e| e.run_id).collect();
+        assert_eq!(remaining, vec![format!("{}-3", now)]);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::error::{Error, Result};
+use crate::project::SKIPPED_DIRS;
+use glob::Pattern;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Build artifacts that should not survive a clean of a C project
+///
+/// Patterns are matched against file and directory names. Tracked files are
+/// never reported, so a hand-written `config.h` under git is fine.
+pub const DEFAULT_ARTIFACT_PATTERNS: &[&str] = &[
+    "*.o", "*.obj", "*.lo", "*.la", "*.a", "*.lib", "*.so", "*.so.*", "*.dylib", "*.dll",
+    "*.d", "*.gch", "*.pch", "*.i", "*.ii", "*.gcno", "*.gcda",
+    "config.h", "config.status", "config.log", "CMakeCache.txt", "CMakeFiles", ".deps", ".libs",
+];
+
+/// Why a path was reported as left over
+#[derive(Debug, Clone, PartialEq)]
+pub enum LeftoverReason {
+    /// The name matches an artifact pattern
+    Artifact(String),
+    /// Not tracked by git
+    Untracked,
+}
+
+/// A file or directory that survived the clean
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leftover {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub reason: LeftoverReason,
+}
+
+impl std::fmt::Display for Leftover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let suffix = if self.is_dir { "/" } else { "" };
+        match &self.reason {
+            LeftoverReason::Artifact(pattern) => {
+                write!(f, "{}{} (matches {})", self.path.display(), suffix, pattern)
+            }
+            LeftoverReason::Untracked => {
+                write!(f, "{}{} (not tracked by git)", self.path.display(), suffix)
+            }
+        }
+    }
+}
+
+/// Files under `project_root` that are tracked by git, relative to the project root
+struct TrackedFiles {
+    files: HashSet<PathBuf>,
+    /// Every directory containing at least one tracked file
+    dirs: HashSet<PathBuf>,
+}
+
+impl TrackedFiles {
+    fn load(project_root: &Path) -> Option<Self> {
+        let repo = git2::Repository::discover(project_root).ok()?;
+        let workdir = repo.workdir()?.canonicalize().ok()?;
+        let root = project_root.canonicalize().ok()?;
+        let index = repo.index().ok()?;
+
+        let mut tracked = TrackedFiles {
+            files: HashSet::new(),
+            dirs: HashSet::new(),
+        };
+        for entry in index.iter() {
+            let path = workdir.join(String::from_utf8_lossy(&entry.path).as_ref());
+            let Ok(relative) = path.strip_prefix(&root) else {
+                continue;
+            };
+            tracked.dirs.extend(relative.ancestors().skip(1).map(Path::to_path_buf));
+            tracked.files.insert(relative.to_path_buf());
+        }
+        Some(tracked)
+    }
+}
+
+/// Scan `dir` (relative to `project_root`) for files the clean left behind
+///
+/// A path is reported when it is not tracked by git and either matches one of
+/// `patterns` or, inside a git repository, is untracked. Untracked
+/// directories without any tracked file are reported as a whole.
+pub fn find_leftovers(project_root: &Path, dir: &str, patterns: &[String]) -> Result<Vec<Leftover>> {
+    let patterns = patterns
+        .iter()
+        .map(|p| {
+            Pattern::new(p).map_err(|e| Error::InvalidArgument(format!("invalid artifact pattern '{}': {}", p, e)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let start = match dir {
+        "." => PathBuf::new(),
+        dir => PathBuf::from(dir),
+    };
+    if !project_root.join(&start).is_dir() {
+        return Err(Error::InvalidArgument(format!(
+            "clean directory '{}' does not exist",
+            project_root.join(&start).display()
+        )));
+    }
+
+    let tracked = TrackedFiles::load(project_root);
+    let mut leftovers = Vec::new();
+    scan(project_root, &start, &patterns, tracked.as_ref(), &mut leftovers)?;
+    Ok(leftovers)
+}
+
+fn scan(
+    project_root: &Path,
+    relative: &Path,
+    patterns: &[Pattern],
+    tracked: Option<&TrackedFiles>,
+    leftovers: &mut Vec<Leftover>,
+) -> Result<()> {
+    let mut entries = std::fs::read_dir(project_root.join(relative))?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let path = relative.join(name.as_ref());
+        let is_dir = entry.file_type()?.is_dir();
+        if is_dir && SKIPPED_DIRS.contains(&name.as_ref()) {
+            continue;
+        }
+
+        let is_tracked = tracked.is_some_and(|t| {
+            if is_dir { t.dirs.contains(&path) } else { t.files.contains(&path) }
+        });
+        let matched = patterns.iter().find(|p| p.matches(&name));
+        let reason = match (is_tracked, matched) {
+            (true, _) => None,
+            (false, Some(pattern)) => Some(LeftoverReason::Artifact(pattern.as_str().to_string())),
+            (false, None) if tracked.is_some() => Some(LeftoverReason::Untracked),
+            (false, None) => None,
+        };
+
+        match reason {
+            // Empty untracked directories are harmless and not worth reporting
+            Some(LeftoverReason::Untracked) if is_dir && !contains_files(&entry.path())? => {}
+            Some(reason) => leftovers.push(Leftover { path, is_dir, reason }),
+            None if is_dir => scan(project_root, &path, patterns, tracked, leftovers)?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn contains_files(dir: &Path) -> Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() || contains_files(&entry.path())? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Fail with [`Error::NotClean`] when `dir` has leftovers
+pub fn verify_dir(project_root: &Path, dir: &str, patterns: &[String]) -> Result<()> {
+    let leftovers = find_leftovers(project_root, dir, patterns)?;
+    if leftovers.is_empty() {
+        return Ok(());
+    }
+    Err(Error::NotClean {
+        dir: dir.to_string(),
+        leftovers: leftovers.iter().map(Leftover::to_string).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_ARTIFACT_PATTERNS.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_artifacts_without_git() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("build/obj")).unwrap();
+        fs::write(root.join("build/main.c"), "").unwrap();
+        fs::write(root.join("build/obj/main.o"), "").unwrap();
+        fs::write(root.join("build/libfoo.so.1"), "").unwrap();
+
+        let leftovers = find_leftovers(root, "build", &defaults()).unwrap();
+        let paths: Vec<_> = leftovers.iter().map(|l| l.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("build/libfoo.so.1"), PathBuf::from("build/obj/main.o")]);
+        assert_eq!(leftovers[1].reason, LeftoverReason::Artifact("*.o".to_string()));
+    }
+
+    #[test]
+    fn test_untracked_files_in_git_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let repo = git2::Repository::init(root).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.c"), "").unwrap();
+        fs::write(root.join("src/config.h"), "").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("src/main.c")).unwrap();
+        index.add_path(Path::new("src/config.h")).unwrap();
+        index.write().unwrap();
+
+        fs::write(root.join("src/generated.h"), "").unwrap();
+        fs::create_dir_all(root.join("src/out/deep")).unwrap();
+        fs::write(root.join("src/out/deep/a.txt"), "").unwrap();
+        fs::create_dir_all(root.join("src/empty")).unwrap();
+
+        let leftovers = find_leftovers(root, ".", &defaults()).unwrap();
+        let rendered: Vec<String> = leftovers.iter().map(Leftover::to_string).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "src/generated.h (not tracked by git)".to_string(),
+                "src/out/ (not tracked by git)".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_dir_reports_error() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("stale.o"), "").unwrap();
+        let err = verify_dir(temp_dir.path(), ".", &defaults()).unwrap_err();
+        assert!(matches!(err, Error::NotClean { ref leftovers, .. } if leftovers.len() == 1));
+        assert!(verify_dir(temp_dir.path(), ".", &["*.obj".to_string()]).is_ok());
+    }
+}
@@ -1,8 +1,12 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use c2rust_clean::config_store::ConfigBackend;
+use c2rust_clean::error::Error;
+use c2rust_clean::runner::{RecordingRunner, Response, Runner};
+use c2rust_clean::{CleanSession, CleanSessionBuilder};
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 /// Helper function to create a mock c2rust-config script for testing
@@ -39,12 +43,78 @@ fn create_mock_c2rust_config(temp_dir: &TempDir) -> PathBuf {
     }
 }
 
+/// Helper function to create a mock c2rust-config script that appends its arguments to `log_file`
+#[cfg(unix)]
+fn create_logging_mock_c2rust_config(temp_dir: &TempDir, log_file: &std::path::Path) -> PathBuf {
+    let mock_script = temp_dir.path().join("mock-c2rust-config-logging");
+
+    let mut script = fs::File::create(&mock_script).unwrap();
+    writeln!(script, "#!/bin/bash").unwrap();
+    writeln!(script, "if [ \"$1\" = \"--help\" ]; then").unwrap();
+    writeln!(script, "  exit 0").unwrap();
+    writeln!(script, "fi").unwrap();
+    writeln!(script, "echo \"$@\" >> {:?}", log_file).unwrap();
+    writeln!(script, "exit 0").unwrap();
+
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(&mock_script).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&mock_script, perms).unwrap();
+
+    mock_script
+}
+
+/// Mock c2rust-config that remembers `--set` values, answers `--get` and
+/// handles `--unset`. Setting the key in `MOCK_FAIL_KEY` fails.
+#[cfg(unix)]
+fn create_stateful_mock_c2rust_config(temp_dir: &TempDir) -> PathBuf {
+    let mock_script = temp_dir.path().join("mock-c2rust-config-stateful");
+    let store = temp_dir.path().join("mock-config-store");
+
+    let mut script = fs::File::create(&mock_script).unwrap();
+    writeln!(script, "#!/bin/bash").unwrap();
+    writeln!(script, "if [ \"$1\" = \"--help\" ]; then exit 0; fi").unwrap();
+    writeln!(script, "store={:?}", store).unwrap();
+    writeln!(script, "feature=default; op=; key=").unwrap();
+    writeln!(script, "shift").unwrap();
+    writeln!(script, "while [ $# -gt 0 ]; do").unwrap();
+    writeln!(script, "  case \"$1\" in").unwrap();
+    writeln!(script, "    --feature) feature=\"$2\"; shift ;;").unwrap();
+    writeln!(script, "    --set|--get|--unset) op=\"$1\"; key=\"$2\"; shift 2; break ;;").unwrap();
+    writeln!(script, "  esac").unwrap();
+    writeln!(script, "  shift").unwrap();
+    writeln!(script, "done").unwrap();
+    writeln!(script, "touch \"$store\"").unwrap();
+    writeln!(script, "if [ \"$op\" = --set ] && [ \"$key\" = \"$MOCK_FAIL_KEY\" ]; then").unwrap();
+    writeln!(script, "  echo \"cannot write $key\" >&2; exit 1").unwrap();
+    writeln!(script, "elif [ \"$op\" = --set ]; then").unwrap();
+    writeln!(script, "  grep -vF \"$feature $key=\" \"$store\" > \"$store.tmp\"").unwrap();
+    writeln!(script, "  echo \"$feature $key=$*\" >> \"$store.tmp\"").unwrap();
+    writeln!(script, "  mv \"$store.tmp\" \"$store\"").unwrap();
+    writeln!(script, "elif [ \"$op\" = --unset ]; then").unwrap();
+    writeln!(script, "  grep -vF \"$feature $key=\" \"$store\" > \"$store.tmp\"").unwrap();
+    writeln!(script, "  mv \"$store.tmp\" \"$store\"").unwrap();
+    writeln!(script, "elif [ \"$op\" = --get ]; then").unwrap();
+    writeln!(script, "  line=$(grep -F \"$feature $key=\" \"$store\" | tail -n 1)").unwrap();
+    writeln!(script, "  [ -z \"$line\" ] && exit 1").unwrap();
+    writeln!(script, "  echo \"${{line#*=}}\"").unwrap();
+    writeln!(script, "fi").unwrap();
+    writeln!(script, "exit 0").unwrap();
+
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(&mock_script).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&mock_script, perms).unwrap();
+
+    mock_script
+}
+
 #[test]
 fn test_clean_command_basic() {
     let temp_dir = TempDir::new().unwrap();
     let mock_config = create_mock_c2rust_config(&temp_dir);
     
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(temp_dir.path())
@@ -61,7 +131,7 @@ fn test_clean_with_multiple_args() {
     let temp_dir = TempDir::new().unwrap();
     let mock_config = create_mock_c2rust_config(&temp_dir);
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(temp_dir.path())
@@ -79,7 +149,7 @@ fn test_clean_with_hyphenated_args() {
     let temp_dir = TempDir::new().unwrap();
     let mock_config = create_mock_c2rust_config(&temp_dir);
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(temp_dir.path())
@@ -95,7 +165,7 @@ fn test_clean_with_hyphenated_args() {
 fn test_missing_clean_cmd() {
     let temp_dir = TempDir::new().unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     // Without any command arguments, should fail (CLEAN_CMD is required)
     cmd.current_dir(temp_dir.path())
@@ -115,7 +185,7 @@ fn test_separator_optional_for_simple_commands() {
     let temp_dir = TempDir::new().unwrap();
     let mock_config = create_mock_c2rust_config(&temp_dir);
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     // Without --, still works for simple commands without flags
     cmd.env("C2RUST_CONFIG", &mock_config)
@@ -136,7 +206,7 @@ fn test_project_root_fallback() {
     
     // Don't create .c2rust directory - should use current dir as project root
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(temp_dir.path())
@@ -151,9 +221,41 @@ fn test_project_root_fallback() {
         .stderr(predicate::str::contains("Relative clean directory: ."));
 }
 
+#[test]
+#[cfg(unix)]
+fn test_out_of_tree_cmake_build_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_file = temp_dir.path().join("config.log");
+    let mock_config = create_logging_mock_c2rust_config(&temp_dir, &log_file);
+
+    let source = temp_dir.path().join("src");
+    let build = temp_dir.path().join("build");
+    fs::create_dir_all(source.join(".c2rust")).unwrap();
+    fs::create_dir_all(build.join("CMakeFiles")).unwrap();
+    fs::write(
+        build.join("CMakeCache.txt"),
+        format!("CMAKE_HOME_DIRECTORY:INTERNAL={}\n", source.canonicalize().unwrap().display()),
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&build)
+        .args(["clean", "--", "rm", "-rf", "CMakeFiles"]);
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Out-of-tree CMake build directory"))
+        .stderr(predicate::str::contains("Relative clean directory: ../build"));
+
+    assert!(!build.join("CMakeFiles").exists());
+    let log = fs::read_to_string(&log_file).unwrap();
+    assert!(log.contains("--set clean.dir ../build"), "{}", log);
+}
+
 #[test]
 fn test_help_output() {
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.arg("--help");
 
@@ -165,7 +267,7 @@ fn test_help_output() {
 
 #[test]
 fn test_clean_subcommand_help() {
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.arg("clean").arg("--help");
 
@@ -187,7 +289,7 @@ fn test_project_root_detection() {
     let sub_dir = temp_dir.path().join("subdir");
     fs::create_dir(&sub_dir).unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(&sub_dir)
@@ -211,7 +313,7 @@ fn test_command_execution_in_current_dir() {
     let test_file = temp_dir.path().join("test.txt");
     fs::write(&test_file, "test content").unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(temp_dir.path())
@@ -230,7 +332,7 @@ fn test_config_tool_not_found() {
     // the CLI exits with ConfigToolNotFound error
     let temp_dir = TempDir::new().unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.current_dir(temp_dir.path())
         .env("C2RUST_CONFIG", "/nonexistent/path/to/c2rust-config")
@@ -244,6 +346,203 @@ fn test_config_tool_not_found() {
         .stderr(predicate::str::contains("c2rust-config not found"));
 }
 
+#[test]
+fn test_native_config_backend() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .env("C2RUST_CONFIG", "/nonexistent/path/to/c2rust-config")
+        .args(["clean", "--config-backend", "native", "--feature", "debug", "--", "echo", "test"]);
+    cmd.assert().success();
+
+    let config = fs::read_to_string(temp_dir.path().join(".c2rust/clean/config.toml")).unwrap();
+    assert!(config.contains("[feature.debug.clean]"), "{}", config);
+    assert!(config.contains("cmd = \"echo test\""), "{}", config);
+}
+
+#[test]
+fn test_config_show_all_features() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join(".c2rust")).unwrap();
+    fs::create_dir_all(root.join("build")).unwrap();
+    fs::create_dir_all(root.join("old")).unwrap();
+
+    for (dir, feature) in [("build", "default"), ("old", "legacy")] {
+        let mut cmd = cargo_bin_cmd!("c2rust-clean");
+        cmd.current_dir(root.join(dir))
+            .args(["clean", "--config-backend", "native", "--feature", feature, "--", "echo", "clean"]);
+        cmd.assert().success();
+    }
+    fs::remove_dir(root.join("old")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(root)
+        .args(["config", "show", "--all-features", "--config-backend", "native"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Feature 'default':"))
+        .stdout(predicate::str::contains("clean.dir:       build\n"))
+        .stdout(predicate::str::contains("clean.dir:       old  ⚠ directory does not exist"))
+        .stderr(predicate::str::contains("1 feature(s) have a clean.dir that no longer exists"));
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(root)
+        .args(["config", "show", "--feature", "legacy", "--json", "--config-backend", "native"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json[0]["feature"], "legacy");
+    assert_eq!(json[0]["cmd"], "echo clean");
+    assert_eq!(json[0]["dir_exists"], false);
+}
+
+#[test]
+fn test_feature_list_copy_delete() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join(".c2rust")).unwrap();
+    let c2rust_clean = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("c2rust-clean");
+        cmd.current_dir(root)
+            .args(args)
+            .args(["--config-backend", "native"]);
+        cmd.assert()
+    };
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(root)
+        .args(["clean", "--config-backend", "native", "--feature", "release", "--", "echo", "distclean"]);
+    cmd.assert().success();
+
+    c2rust_clean(&["feature", "copy", "release", "debug"]).success();
+    c2rust_clean(&["feature", "copy", "release", "debug"])
+        .failure()
+        .stderr(predicate::str::contains("pass --force"));
+    c2rust_clean(&["feature", "list"])
+        .success()
+        .stdout(predicate::str::contains("debug"))
+        .stdout(predicate::str::contains("release              .: echo distclean"));
+
+    c2rust_clean(&["feature", "delete", "release"]).success();
+    c2rust_clean(&["feature", "list"])
+        .success()
+        .stdout(predicate::str::contains("release").not())
+        .stdout(predicate::str::contains("debug"));
+    c2rust_clean(&["feature", "delete", "release"])
+        .failure()
+        .stderr(predicate::str::contains("has no saved clean configuration"));
+
+    c2rust_clean(&["feature", "copy", "debug", "my feature"])
+        .failure()
+        .stderr(predicate::str::contains("must not contain whitespace"));
+
+    // Dynamic shell completion offers the existing feature names
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(root)
+        .env("COMPLETE", "bash")
+        .env("_CLAP_COMPLETE_INDEX", "3")
+        .env("_CLAP_IFS", "\n")
+        .args(["--", "c2rust-clean", "feature", "delete", ""]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("debug\n"))
+        .stdout(predicate::str::contains("release").not());
+}
+
+#[test]
+fn test_config_backend_falls_back_to_native() {
+    // Without C2RUST_CONFIG and without c2rust-config on PATH the native file is used
+    let temp_dir = TempDir::new().unwrap();
+    let empty_bin = TempDir::new().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .env_remove("C2RUST_CONFIG")
+        .env("PATH", empty_bin.path())
+        .args(["clean", "--", "/bin/true"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("c2rust-config not found, using the native configuration file"));
+    assert!(temp_dir.path().join(".c2rust/clean/config.toml").exists());
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .env_remove("C2RUST_CONFIG")
+        .env("PATH", empty_bin.path())
+        .args(["clean", "--config-backend", "c2rust-config", "--", "/bin/true"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("c2rust-config not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_tool_probe_is_cached() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_file = temp_dir.path().join("config.log");
+    let mock_config = create_logging_mock_c2rust_config(&temp_dir, &log_file);
+    let cache_home = temp_dir.path().join("cache");
+
+    let clean = || {
+        let mut cmd = cargo_bin_cmd!("c2rust-clean");
+        cmd.env("C2RUST_CONFIG", &mock_config)
+            .env("XDG_CACHE_HOME", &cache_home)
+            .current_dir(temp_dir.path())
+            .args(["clean", "--", "echo", "test"]);
+        cmd.assert().success();
+    };
+    let probes = || {
+        fs::read_to_string(&log_file)
+            .unwrap()
+            .lines()
+            .filter(|line| *line == "--version")
+            .count()
+    };
+
+    clean();
+    clean();
+    assert_eq!(probes(), 1);
+    assert!(cache_home.join("c2rust-clean/config-tool.json").exists());
+
+    // A rebuilt binary is probed again
+    let script = fs::read_to_string(&mock_config).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::write(&mock_config, script).unwrap();
+    clean();
+    assert_eq!(probes(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_tool_incompatible_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_script = temp_dir.path().join("old-c2rust-config");
+    fs::write(
+        &mock_script,
+        "#!/bin/bash\n\
+         if [ \"$1\" = --version ]; then echo 'c2rust-config 0.1.0'; exit 0; fi\n\
+         if [ \"$1\" = --help ]; then echo 'Commands: config, build'; exit 0; fi\n\
+         if [ \"$2\" = --help ]; then echo 'Options: --set <KEY> --get <KEY> --help'; exit 0; fi\n\
+         exit 0\n",
+    )
+    .unwrap();
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(&mock_script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_script)
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .current_dir(temp_dir.path())
+        .args(["clean", "--", "echo", "test"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Incompatible c2rust-config"))
+        .stderr(predicate::str::contains("(0.1.0) does not support `config --make`, `config --feature`"))
+        .stderr(predicate::str::contains("--config-backend native"));
+}
+
 #[test]
 fn test_feature_parameter_with_mock_config() {
     // Test that --feature parameter is passed through to c2rust-config
@@ -280,7 +579,7 @@ fn test_feature_parameter_with_mock_config() {
         writeln!(script, "exit /b 0").unwrap();
     }
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     #[cfg(unix)]
     cmd.env("C2RUST_CONFIG", &mock_script);
@@ -341,7 +640,7 @@ fn test_default_feature_when_not_specified() {
         writeln!(script, "exit /b 0").unwrap();
     }
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     #[cfg(unix)]
     cmd.env("C2RUST_CONFIG", &mock_script);
@@ -379,7 +678,7 @@ fn test_git_auto_commit_failure_is_non_fatal() {
     // Create a file to trigger commit attempt
     fs::write(c2rust_dir.join("test.txt"), "content").unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(temp_dir.path())
@@ -409,7 +708,7 @@ fn test_auto_detect_git_root() {
     let sub_dir = temp_dir.path().join("subdir");
     fs::create_dir(&sub_dir).unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(&sub_dir)
@@ -437,7 +736,7 @@ fn test_auto_detect_cargo_toml_root() {
     let sub_dir = temp_dir.path().join("src");
     fs::create_dir(&sub_dir).unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(&sub_dir)
@@ -468,7 +767,7 @@ fn test_auto_detect_c2rust_marker() {
     let sub_dir2 = sub_dir1.join("level2");
     fs::create_dir(&sub_dir2).unwrap();
 
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(&sub_dir2)
@@ -499,7 +798,7 @@ fn test_marker_priority_closest_marker_wins() {
     fs::write(sub_dir.join("Cargo.toml"), "[package]\nname = \"subproject\"\n").unwrap();
     
     // Run from the subdirectory - should find Cargo.toml first (closer)
-    let mut cmd = Command::cargo_bin("c2rust-clean").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
     
     cmd.env("C2RUST_CONFIG", &mock_config)
         .current_dir(&sub_dir)
@@ -513,3 +812,1141 @@ fn test_marker_priority_closest_marker_wins() {
         .stderr(predicate::str::contains(format!("Project root: {}", sub_dir.display())))
         .stderr(predicate::str::contains("Relative clean directory: ."));
 }
+
+#[cfg(unix)]
+#[test]
+fn test_pty_log_with_strip_ansi() {
+    // Test that --pty gives the command a terminal and --log/--strip-ansi save a plain copy
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let log_file = temp_dir.path().join("clean.log");
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--pty")
+        .arg("--log")
+        .arg(&log_file)
+        .arg("--strip-ansi")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("test -t 1 && printf '\\033[31mremoved\\033[0m\\n'");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[31mremoved"));
+
+    let log_content = fs::read_to_string(&log_file).unwrap();
+    assert!(log_content.contains("removed"), "Expected command output in log");
+    assert!(!log_content.contains('\x1b'), "Expected ANSI sequences to be stripped");
+}
+
+#[test]
+fn test_strip_ansi_requires_log() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--strip-ansi")
+        .arg("--")
+        .arg("echo")
+        .arg("test");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--log"));
+}
+
+#[test]
+fn test_steps_conflict_with_clean_cmd() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--step")
+        .arg("make clean")
+        .arg("--")
+        .arg("make")
+        .arg("clean");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_each_cleans_matching_directories() {
+    // Test that --each runs the command in every matching directory and saves per-root config
+    let temp_dir = TempDir::new().unwrap();
+    let log_file = temp_dir.path().join("config.log");
+    let mock_config = create_logging_mock_c2rust_config(&temp_dir, &log_file);
+    fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+    for lib in ["liba", "libb"] {
+        fs::create_dir(temp_dir.path().join(lib)).unwrap();
+        fs::write(temp_dir.path().join(lib).join("x.o"), "").unwrap();
+    }
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--each")
+        .arg("lib*")
+        .arg("-j")
+        .arg("2")
+        .arg("--")
+        .arg("rm")
+        .arg("-f")
+        .arg("x.o");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Summary: 2 passed, 0 failed"));
+
+    assert!(!temp_dir.path().join("liba").join("x.o").exists());
+    assert!(!temp_dir.path().join("libb").join("x.o").exists());
+
+    let log_content = fs::read_to_string(&log_file).unwrap();
+    assert!(log_content.contains("--set clean.steps liba:rm -f x.o\nlibb:rm -f x.o"),
+            "Expected both directories saved as steps, got: {}", log_content);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_each_reports_failures() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    for lib in ["good", "bad"] {
+        fs::create_dir(temp_dir.path().join(lib)).unwrap();
+    }
+    fs::write(temp_dir.path().join("good").join("Makefile.ok"), "").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--each")
+        .arg("*")
+        .arg("--")
+        .arg("ls")
+        .arg("Makefile.ok");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Summary: 1 passed, 1 failed"))
+        .stderr(predicate::str::contains("Clean failed in 1 directory"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_each_uses_the_limits_of_each_project_root() {
+    let temp_dir = TempDir::new().unwrap();
+    for (project, nofile) in [("p1", "64"), ("p2", "128")] {
+        let dir = temp_dir.path().join(project);
+        fs::create_dir_all(dir.join(".c2rust")).unwrap();
+        cargo_bin_cmd!("c2rust-clean")
+            .current_dir(&dir)
+            .args(["clean", "--config-backend", "native", "--limit-nofile", nofile, "--", "true"])
+            .assert()
+            .success();
+    }
+
+    cargo_bin_cmd!("c2rust-clean")
+        .current_dir(temp_dir.path())
+        .args(["clean", "--config-backend", "native", "--each", "p*", "--", "sh", "-c", "ulimit -n"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("p1 (exit code 0)\n64\n"))
+        .stdout(predicate::str::contains("p2 (exit code 0)\n128\n"));
+
+    cargo_bin_cmd!("c2rust-clean")
+        .current_dir(temp_dir.path())
+        .args(["clean", "--each", "p*", "--compdb", "db.json", "--", "true"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_resource_limits_applied_and_saved() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_file = temp_dir.path().join("config.log");
+    let mock_config = create_logging_mock_c2rust_config(&temp_dir, &log_file);
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--limit-nofile")
+        .arg("64")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("test \"$(ulimit -n)\" = 64");
+
+    cmd.assert().success();
+
+    let log_content = fs::read_to_string(&log_file).unwrap();
+    assert!(log_content.contains("--set clean.limits nofile=64"),
+            "Expected limits to be saved, got: {}", log_content);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cpu_limit_exceeded_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--limit-cpu")
+        .arg("1")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("while :; do :; done");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Resource limit exceeded"));
+}
+
+#[test]
+fn test_run_metadata_records_timing() {
+    // Test that timing is shown and stored in .c2rust/clean/runs/<run-id>.json
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--")
+        .arg("echo")
+        .arg("test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Time: wall "))
+        .stdout(predicate::str::contains("✓ Run metadata saved to"));
+
+    let runs_dir = temp_dir.path().join(".c2rust").join("clean").join("runs");
+    let records: Vec<_> = fs::read_dir(&runs_dir).unwrap().collect();
+    assert_eq!(records.len(), 1);
+    let content = fs::read_to_string(records[0].as_ref().unwrap().path()).unwrap();
+    assert!(content.contains("\"command\": \"echo test\""), "unexpected record: {}", content);
+    assert!(content.contains("\"wall_secs\""));
+    assert!(content.contains("\"peak_rss_bytes\""));
+}
+
+#[test]
+fn test_safety_guard_rejects_escaping_rm() {
+    // Test that rm targets outside the project root are refused before execution
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+    let victim = temp_dir.path().join("victim.txt");
+    fs::write(&victim, "keep me").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--")
+        .arg("rm")
+        .arg("-f")
+        .arg("../victim.txt");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsafe clean command"))
+        .stderr(predicate::str::contains("outside the project root"));
+
+    assert!(victim.exists(), "Unsafe command must not be executed");
+}
+
+#[test]
+fn test_safety_guard_protects_c2rust_dir_and_can_be_overridden() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust").join("cache")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--")
+        .arg("rm")
+        .arg("-rf")
+        .arg(".c2rust/cache");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("protected path"));
+    assert!(project.join(".c2rust").join("cache").exists());
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--i-know-what-im-doing")
+        .arg("--")
+        .arg("rm")
+        .arg("-rf")
+        .arg(".c2rust/cache");
+
+    cmd.assert().success();
+    assert!(!project.join(".c2rust").join("cache").exists());
+}
+
+/// Helper function to create a git repository in `dir` with `files` committed
+fn init_git_repo_with_files(dir: &std::path::Path, files: &[&str]) {
+    let repo = git2::Repository::init(dir).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", "Test User").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+
+    for name in files {
+        fs::write(dir.join(name), format!("/* {} */\n", name)).unwrap();
+    }
+    let mut index = repo.index().unwrap();
+    index.add_all(["."].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = repo.signature().unwrap();
+    repo.commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[]).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clean_deleting_tracked_sources_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    init_git_repo_with_files(&project, &["main.c", "main.h"]);
+    fs::write(project.join("main.o"), "").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("rm -f *.o *.c");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("deleted or modified 1 git-tracked file(s)"))
+        .stderr(predicate::str::contains("main.c"));
+
+    assert!(!project.join("main.c").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clean_restores_tracked_sources() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    init_git_repo_with_files(&project, &["main.c"]);
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--restore-sources")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("rm -f *.c");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("restored to their state before the clean"));
+
+    assert_eq!(fs::read_to_string(project.join("main.c")).unwrap(), "/* main.c */\n");
+}
+
+#[test]
+fn test_trash_and_restore() {
+    // Test that --trash keeps removed files and `restore` puts them back
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+    let build = project.join("build");
+    fs::create_dir(&build).unwrap();
+    fs::write(build.join("config.h"), "hand edited").unwrap();
+    fs::write(build.join("keep.txt"), "keep").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&build)
+        .arg("clean")
+        .arg("--trash")
+        .arg("--")
+        .arg("rm")
+        .arg("-f")
+        .arg("config.h");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Moved 1 removed file(s)"));
+    assert!(!build.join("config.h").exists());
+
+    let trash_dir = project.join(".c2rust").join("trash");
+    let run_ids: Vec<String> = fs::read_dir(&trash_dir)
+        .unwrap()
+        .map(|e| e.unwrap())
+        .filter(|e| e.file_type().unwrap().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(run_ids.len(), 1);
+    assert!(trash_dir.join(&run_ids[0]).join("build").join("config.h").exists());
+    assert!(!trash_dir.join(&run_ids[0]).join("build").join("keep.txt").exists());
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(&project).arg("restore");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(run_ids[0].as_str()));
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(&project).arg("restore").arg(&run_ids[0]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Restored 1 file(s)"));
+
+    assert_eq!(fs::read_to_string(build.join("config.h")).unwrap(), "hand edited");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sandbox_limits_writes_to_clean_dir() {
+    // Test that --sandbox keeps the clean command from writing outside its directory
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    let build = project.join("build");
+    fs::create_dir_all(&build).unwrap();
+    fs::write(build.join("main.o"), "object").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&build)
+        .arg("clean")
+        .arg("--sandbox")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("rm -f main.o && touch ../stray.txt");
+
+    let output = cmd.output().unwrap();
+    if String::from_utf8_lossy(&output.stderr).contains("Sandbox unavailable") {
+        eprintln!("skipping: namespace sandbox unavailable on this system");
+        return;
+    }
+
+    assert!(output.status.success(), "clean failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Sandbox: only"));
+    assert!(!build.join("main.o").exists());
+    assert!(!project.join("stray.txt").exists());
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[test]
+fn test_trace_deletions_writes_ledger() {
+    // Test that --trace-deletions records files created and removed within the run
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+    let build = project.join("build");
+    fs::create_dir(&build).unwrap();
+    fs::write(build.join("main.o"), "object").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&build)
+        .arg("clean")
+        .arg("--trace-deletions")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("touch scratch.tmp && rm -f scratch.tmp main.o");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Traced 2 deletion(s)"))
+        .stdout(predicate::str::contains("Deletion ledger saved to"));
+
+    let runs_dir = project.join(".c2rust").join("clean").join("runs");
+    let ledger = fs::read_dir(&runs_dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .find(|p| p.to_string_lossy().ends_with(".deletions.jsonl"))
+        .expect("ledger file");
+    let content = fs::read_to_string(ledger).unwrap();
+    assert_eq!(content.lines().count(), 2, "unexpected ledger: {}", content);
+    assert!(content.contains("scratch.tmp") && content.contains("main.o"));
+    assert!(content.contains("\"command\":\"rm -f scratch.tmp main.o\""));
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[test]
+fn test_trace_deletions_without_c2rust_dir() {
+    // Test that a daemon started by the clean does not keep it waiting,
+    // and that no .c2rust directory is created for the ledger
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    fs::write(project.join("Cargo.toml"), "").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--trace-deletions")
+        .arg("--")
+        .arg("sh")
+        .arg("-c")
+        .arg("sleep 30 >/dev/null 2>&1 & echo $! > daemon.pid");
+
+    let started = std::time::Instant::now();
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("not saved since there is no .c2rust directory"))
+        .stdout(predicate::str::contains("Deletion ledger saved to").not());
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+    assert!(!project.join(".c2rust").exists());
+
+    let pid = fs::read_to_string(project.join("daemon.pid")).unwrap();
+    std::process::Command::new("kill").arg(pid.trim()).status().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_drift_requires_update_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_stateful_mock_c2rust_config(&temp_dir);
+    let store = temp_dir.path().join("mock-config-store");
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+
+    let clean = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("c2rust-clean");
+        cmd.env("C2RUST_CONFIG", &mock_config)
+            .current_dir(&project)
+            .arg("clean")
+            .args(args);
+        cmd.assert()
+    };
+
+    clean(&["--", "echo", "distclean"]).success();
+
+    // A different command runs but does not replace the saved one
+    clean(&["--", "echo", "quick"])
+        .success()
+        .stdout(predicate::str::contains("quick"))
+        .stdout(predicate::str::contains("Configuration saved").not())
+        .stderr(predicate::str::contains("- echo distclean"))
+        .stderr(predicate::str::contains("+ echo quick"))
+        .stderr(predicate::str::contains("pass --update-config"));
+    assert!(fs::read_to_string(&store).unwrap().contains("clean.cmd=echo distclean"));
+
+    // --no-save skips the comparison and the save
+    clean(&["--no-save", "--", "echo", "quick"])
+        .success()
+        .stderr(predicate::str::contains("differs").not());
+    assert!(fs::read_to_string(&store).unwrap().contains("clean.cmd=echo distclean"));
+
+    clean(&["--update-config", "--", "echo", "quick"])
+        .success()
+        .stdout(predicate::str::contains("Configuration saved"));
+    assert!(fs::read_to_string(&store).unwrap().contains("clean.cmd=echo quick"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_reports_leftovers() {
+    // Test that `verify` checks the saved clean.dir and `clean --verify` chains it
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_stateful_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+    let build = project.join("build");
+    fs::create_dir(&build).unwrap();
+    fs::write(build.join("main.o"), "object").unwrap();
+    fs::write(build.join("stale.o"), "object").unwrap();
+
+    // The clean misses stale.o, so --verify fails after saving the config
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&build)
+        .arg("clean")
+        .arg("--verify")
+        .arg("--")
+        .arg("rm")
+        .arg("-f")
+        .arg("main.o");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Directory 'build' is not clean, 1 leftover(s)"))
+        .stderr(predicate::str::contains("build/stale.o (matches *.o)"));
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("verify")
+        .arg("--artifact")
+        .arg("*.obj");
+    cmd.assert().success().stdout(predicate::str::contains("build is clean"));
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config).current_dir(&project).arg("verify");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("build/stale.o"));
+
+    fs::remove_file(build.join("stale.o")).unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config).current_dir(&project).arg("verify");
+    cmd.assert().success();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_if_needed_skips_unchanged_directory() {
+    // Test that --if-needed only runs the clean when the directory changed
+    let temp_dir = TempDir::new().unwrap();
+    let log_file = temp_dir.path().join("config.log");
+    let mock_config = create_logging_mock_c2rust_config(&temp_dir, &log_file);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+    let build = project.join("build");
+    fs::create_dir(&build).unwrap();
+    fs::write(build.join("main.o"), "object").unwrap();
+    let counter = temp_dir.path().join("runs.txt");
+    let clean = format!("rm -f *.o && echo run >> {}", counter.display());
+
+    let run_clean = |script: &str| {
+        let mut cmd = cargo_bin_cmd!("c2rust-clean");
+        cmd.env("C2RUST_CONFIG", &mock_config)
+            .current_dir(&build)
+            .arg("clean")
+            .arg("--if-needed")
+            .arg("--")
+            .arg("sh")
+            .arg("-c")
+            .arg(script);
+        cmd.assert().success()
+    };
+
+    run_clean(&clean).stdout(predicate::str::contains("Clean command executed successfully"));
+    run_clean(&clean).stdout(predicate::str::contains("skipping the clean command"));
+    assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 1);
+
+    // A changed command is saved even though the clean is skipped
+    fs::write(&log_file, "").unwrap();
+    let changed = format!("{} # changed", clean);
+    run_clean(&changed)
+        .stdout(predicate::str::contains("skipping the clean command"))
+        .stdout(predicate::str::contains("Configuration saved"));
+    assert!(fs::read_to_string(&log_file).unwrap().contains("# changed"));
+    assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 1);
+
+    // New artifacts make the clean run again
+    fs::write(build.join("util.o"), "object").unwrap();
+    run_clean(&changed).stdout(predicate::str::contains("Clean command executed successfully"));
+    assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 2);
+    assert!(!build.join("util.o").exists());
+}
+
+/// Write a compile_commands.json with two objects built into `dir/obj`
+fn write_compdb(dir: &std::path::Path) -> PathBuf {
+    let compdb = dir.join("compile_commands.json");
+    let json = format!(
+        r#"[
+            {{"directory": "{0}", "file": "a.c", "command": "cc -c -o obj/a.o a.c"}},
+            {{"directory": "{0}", "file": "b.c", "arguments": ["cc", "-c", "-o", "obj/b.o", "b.c"]}}
+        ]"#,
+        dir.display()
+    );
+    fs::write(&compdb, json).unwrap();
+    fs::create_dir_all(dir.join("obj")).unwrap();
+    fs::write(dir.join("obj/a.o"), "object").unwrap();
+    fs::write(dir.join("obj/b.o"), "object").unwrap();
+    compdb
+}
+
+#[test]
+fn test_compdb_reports_remaining_outputs() {
+    // Test that --compdb lists outputs the clean command left behind
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let build = temp_dir.path().join("build");
+    fs::create_dir(&build).unwrap();
+    write_compdb(&build);
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&build)
+        .arg("clean")
+        .arg("--compdb")
+        .arg("--")
+        .arg("rm")
+        .arg("-f")
+        .arg("obj/a.o")
+        .arg("compile_commands.json");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("1 of 2 output(s) listed in compile_commands.json still exist"))
+        .stderr(predicate::str::contains("obj/b.o"));
+}
+
+#[test]
+fn test_from_compdb_removes_outputs() {
+    // Test that --from-compdb deletes the listed outputs without a clean command
+    let temp_dir = TempDir::new().unwrap();
+    let build = temp_dir.path().join("build");
+    fs::create_dir(&build).unwrap();
+    fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+    let compdb = write_compdb(&build);
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--from-compdb")
+        .arg("--compdb")
+        .arg(&compdb);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 2 of 2 output(s)"));
+    assert!(!build.join("obj/a.o").exists());
+    assert!(!build.join("obj/b.o").exists());
+    assert!(compdb.exists());
+}
+
+#[test]
+fn test_from_compdb_trash_and_run_record() {
+    // Test that --from-compdb moves the outputs to the trash and records the run
+    let temp_dir = TempDir::new().unwrap();
+    let build = temp_dir.path().join("build");
+    fs::create_dir(&build).unwrap();
+    fs::create_dir(temp_dir.path().join(".c2rust")).unwrap();
+    let compdb = write_compdb(&build);
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(&build)
+        .args(["clean", "--from-compdb", "--trash", "--compdb"])
+        .arg(&compdb);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Moved 2 removed file(s)"))
+        .stdout(predicate::str::contains("Run metadata saved to"));
+    assert!(!build.join("obj/a.o").exists());
+
+    let trash = temp_dir.path().join(".c2rust/trash");
+    let run = fs::read_dir(&trash)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.is_dir())
+        .unwrap();
+    assert!(run.join("build/obj/a.o").exists());
+    assert!(run.join("build/obj/b.o").exists());
+    let record = temp_dir
+        .path()
+        .join(".c2rust/clean/runs")
+        .join(format!("{}.json", run.file_name().unwrap().to_str().unwrap()));
+    assert!(fs::read_to_string(record).unwrap().contains("--from-compdb"));
+
+    // Options that cannot apply to the built-in clean are rejected
+    for flag in ["--sandbox", "--if-needed", "--trace-deletions"] {
+        let mut cmd = cargo_bin_cmd!("c2rust-clean");
+        cmd.current_dir(&build).args(["clean", "--from-compdb", flag]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+fn make_available() -> bool {
+    std::process::Command::new("make")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_make_dry_run_missing_target() {
+    // Test that a Makefile without a clean target fails before running anything
+    if !make_available() {
+        return;
+    }
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    fs::write(temp_dir.path().join("Makefile"), "all:\n\techo building\n").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(temp_dir.path())
+        .arg("clean")
+        .arg("--")
+        .arg("make")
+        .arg("clean");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Executing command").not())
+        .stderr(predicate::str::contains("has no 'clean' target"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_make_dry_run_flags_dangerous_commands() {
+    // Test that `make -n` output is shown and dangerous recursive commands are rejected
+    if !make_available() {
+        return;
+    }
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = create_mock_c2rust_config(&temp_dir);
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(project.join(".c2rust")).unwrap();
+    fs::create_dir_all(project.join("sub")).unwrap();
+    fs::write(project.join("Makefile"), "clean:\n\trm -f *.o\n\t$(MAKE) -C sub clean\n").unwrap();
+    fs::write(project.join("sub").join("Makefile"), "clean:\n\trm -rf ../..\n").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--")
+        .arg("make")
+        .arg("clean");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("Dry run of 'make clean' would execute:"))
+        .stdout(predicate::str::contains("  rm -f *.o"))
+        .stdout(predicate::str::contains("[sub] rm -rf ../.."))
+        .stderr(predicate::str::contains("would run 1 dangerous command(s): rm -rf ../.."));
+    assert!(project.exists());
+
+    // Without the recursive part the clean runs normally
+    fs::write(project.join("sub").join("Makefile"), "clean:\n\trm -f *.o\n").unwrap();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .current_dir(&project)
+        .arg("clean")
+        .arg("--")
+        .arg("make")
+        .arg("clean");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Clean command executed successfully"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_tool_discovery() {
+    // Without C2RUST_CONFIG or PATH the project-local .c2rust/bin tool is used
+    let temp_dir = TempDir::new().unwrap();
+    let empty_bin = TempDir::new().unwrap();
+    let log_file = temp_dir.path().join("local.log");
+    let mock_config = create_logging_mock_c2rust_config(&temp_dir, &log_file);
+    let project_bin = temp_dir.path().join(".c2rust/bin");
+    fs::create_dir_all(&project_bin).unwrap();
+    fs::rename(&mock_config, project_bin.join("c2rust-config")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .env_remove("C2RUST_CONFIG")
+        .env("PATH", empty_bin.path())
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .args(["clean", "--explain", "--", "/bin/true"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("c2rust-config resolution:"))
+        .stderr(predicate::str::is_match(r"\.c2rust/bin +\S+/\.c2rust/bin/c2rust-config <- used").unwrap())
+        .stderr(predicate::str::contains("not found, using the native").not());
+    assert!(fs::read_to_string(&log_file).unwrap().contains("--set clean.cmd /bin/true"));
+
+    // --config-tool takes precedence over the project-local tool
+    let flag_log = temp_dir.path().join("flag.log");
+    let flag_config = create_logging_mock_c2rust_config(&temp_dir, &flag_log);
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .env_remove("C2RUST_CONFIG")
+        .env("PATH", empty_bin.path())
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .arg("clean")
+        .arg("--config-tool")
+        .arg(&flag_config)
+        .args(["--", "/bin/true"]);
+    cmd.assert().success();
+    assert!(fs::read_to_string(&flag_log).unwrap().contains("--set clean.cmd /bin/true"));
+
+    // A --config-tool that does not exist is an error, not a fallback
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.current_dir(temp_dir.path())
+        .env_remove("C2RUST_CONFIG")
+        .env("PATH", empty_bin.path())
+        .args(["clean", "--config-tool", "/nonexistent/c2rust-config", "--", "/bin/true"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("c2rust-config not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_tool_timeout_and_diagnostics() {
+    // A hung c2rust-config is killed, and failures show the full invocation and output
+    let temp_dir = TempDir::new().unwrap();
+    let mock_config = temp_dir.path().join("mock-c2rust-config-slow");
+    fs::write(
+        &mock_config,
+        "#!/bin/bash\n\
+         if [ \"$1\" = \"--help\" ]; then exit 0; fi\n\
+         case \"$*\" in\n\
+           *'--set clean.dir'*)\n\
+             if [ \"$MOCK_MODE\" = hang ]; then sleep 30; fi\n\
+             echo 'partial write'; echo 'disk full' >&2; exit 4 ;;\n\
+         esac\n\
+         exit 1\n",
+    )
+    .unwrap();
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(&mock_config, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let started = std::time::Instant::now();
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .env("MOCK_MODE", "hang")
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .current_dir(temp_dir.path())
+        .args(["clean", "--config-timeout", "1", "--", "echo", "cleaned"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("cleaned"))
+        .stderr(predicate::str::contains("config --make --feature default --set clean.dir .`"))
+        .stderr(predicate::str::contains("timed out after 1s and was killed"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+
+    let mut cmd = cargo_bin_cmd!("c2rust-clean");
+    cmd.env("C2RUST_CONFIG", &mock_config)
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .current_dir(temp_dir.path())
+        .args(["clean", "--", "echo", "cleaned"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to save clean.dir"))
+        .stderr(predicate::str::contains("exited with code 4"))
+        .stderr(predicate::str::contains("stdout:\n    partial write"))
+        .stderr(predicate::str::contains("stderr:\n    disk full"));
+}
+
+/// A session in `dir` whose commands, c2rust-config included, all go to `recorder`
+fn recording_session(root: &Path, dir: &Path, recorder: &RecordingRunner) -> CleanSessionBuilder {
+    // Only has to exist; the recorder answers in its place
+    let config_tool = root.join("c2rust-config");
+    fs::write(&config_tool, "").unwrap();
+    CleanSession::builder()
+        .dir(dir)
+        .config_backend(ConfigBackend::C2rustConfig)
+        .config_tool(&config_tool)
+        .runner(Runner::new(recorder.clone()))
+}
+
+/// The recorded commands other than the capability probes, with the
+/// c2rust-config path shortened to its name
+fn recorded_lines(root: &Path, recorder: &RecordingRunner) -> Vec<String> {
+    let tool = root.join("c2rust-config").display().to_string();
+    recorder
+        .commands()
+        .iter()
+        .filter(|command| !command.argv.iter().any(|arg| arg == "--help" || arg == "--version"))
+        .map(|command| command.to_string().replace(&tool, "c2rust-config"))
+        .collect()
+}
+
+#[test]
+fn test_clean_session_with_recording_runner() {
+    // The whole clean runs through a recording runner: no process is started,
+    // and the exact commands can be asserted without a mock script
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    fs::create_dir(root.join(".c2rust")).unwrap();
+    let cache_home = temp_dir.path().join("cache");
+    std::env::set_var("XDG_CACHE_HOME", &cache_home);
+    let config_tool = root.join("c2rust-config");
+
+    let recorder = RecordingRunner::new();
+    recorder.respond(&["make", "clean"], Response::success("rm -f *.o\n"));
+    let report = recording_session(&root, &root, &recorder)
+        .command(["make", "clean"])
+        .build()
+        .unwrap()
+        .run()
+        .unwrap();
+    assert!(report.config_saved);
+
+    // Scripted probe answers are never cached, so the probes show up every time
+    let tool = config_tool.display().to_string();
+    let commands = recorder.commands();
+    let lines: Vec<String> = commands.iter().map(|command| command.to_string().replace(&tool, "c2rust-config")).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "c2rust-config --help",
+            "c2rust-config --version",
+            "c2rust-config config --help",
+            "c2rust-config config --make --feature default --get clean.limits",
+            "make clean",
+            "c2rust-config config --make --feature default --get clean.dir",
+            "c2rust-config config --make --feature default --get clean.cmd",
+            "c2rust-config config --make --feature default --set clean.dir .",
+            "c2rust-config config --make --feature default --set clean.cmd 'make clean'",
+        ]
+    );
+    // Past the three probes, everything runs in the project root
+    assert!(commands
+        .iter()
+        .skip(3)
+        .all(|command| command.dir.as_deref() == Some(root.as_path())));
+    assert!(!cache_home.exists());
+}
+
+#[test]
+fn test_multiple_steps_run_in_order() {
+    // Each step runs in its own directory, and the list is saved as one value
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join(".c2rust")).unwrap();
+    fs::create_dir(root.join("tests")).unwrap();
+
+    let recorder = RecordingRunner::new();
+    recording_session(&root, &root, &recorder)
+        .step("rm -f b.o")
+        .step("tests:rm -f a.o")
+        .build()
+        .unwrap()
+        .run()
+        .unwrap();
+
+    let steps: Vec<_> = recorder.commands().into_iter().filter(|command| command.argv[0] == "rm").collect();
+    assert_eq!(steps[0].dir.as_deref(), Some(root.as_path()));
+    assert_eq!(steps[1].dir.as_deref(), Some(root.join("tests").as_path()));
+    let lines = recorded_lines(&root, &recorder);
+    assert!(
+        lines.contains(&"c2rust-config config --make --feature default --set clean.steps '.:rm -f b.o\ntests:rm -f a.o'".to_string()),
+        "{:?}",
+        lines
+    );
+}
+
+#[test]
+fn test_steps_stop_on_first_failure() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join(".c2rust")).unwrap();
+
+    let recorder = RecordingRunner::new();
+    recorder.respond(&["false"], Response::failure(1, ""));
+    let result = recording_session(&root, &root, &recorder)
+        .step("false")
+        .step("rm -f keep.o")
+        .build()
+        .unwrap()
+        .run();
+
+    assert!(matches!(&result, Err(e) if e.to_string().contains("Command 'false ' failed")), "{:?}", result);
+    let lines = recorded_lines(&root, &recorder);
+    assert!(lines.contains(&"false".to_string()), "{:?}", lines);
+    assert!(!lines.contains(&"rm -f keep.o".to_string()), "Second step must not run: {:?}", lines);
+}
+
+#[test]
+fn test_steps_keep_going_from_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join(".c2rust")).unwrap();
+    let steps_file = root.join("steps.txt");
+    fs::write(&steps_file, "# steps\nfalse\nrm -f gone.o\n").unwrap();
+
+    let recorder = RecordingRunner::new();
+    recorder.respond(&["false"], Response::failure(1, ""));
+    let result = recording_session(&root, &root, &recorder)
+        .steps_file(&steps_file)
+        .keep_going(true)
+        .build()
+        .unwrap()
+        .run();
+
+    assert!(matches!(&result, Err(Error::StepsFailed(failed)) if failed == &[".:false"]), "{:?}", result);
+    let lines = recorded_lines(&root, &recorder);
+    assert!(lines.contains(&"rm -f gone.o".to_string()), "Later steps must still run: {:?}", lines);
+}
+
+#[test]
+fn test_config_save_rolls_back_on_partial_failure() {
+    // clean.dir is written first, then clean.cmd fails and clean.dir is restored
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join(".c2rust")).unwrap();
+    fs::create_dir_all(root.join("build")).unwrap();
+
+    let recorder = RecordingRunner::new();
+    recorder.respond(&["--get", "clean.dir"], Response::success(".\n"));
+    recorder.respond(&["--get", "clean.cmd"], Response::success("echo one\n"));
+    recorder.respond(&["--set", "clean.cmd"], Response::failure(1, "cannot write clean.cmd"));
+    let result = recording_session(&root, &root.join("build"), &recorder)
+        .command(["echo", "two"])
+        .build()
+        .unwrap()
+        .run();
+
+    let message = result.unwrap_err().to_string();
+    assert!(message.contains("cannot write clean.cmd"), "{}", message);
+    assert!(message.contains("rolled back clean.dir"), "{}", message);
+    let writes: Vec<String> = recorded_lines(&root, &recorder)
+        .into_iter()
+        .filter(|line| line.contains("--set") || line.contains("--unset"))
+        .collect();
+    assert_eq!(
+        writes,
+        vec![
+            "c2rust-config config --make --feature default --set clean.dir build",
+            "c2rust-config config --make --feature default --set clean.cmd 'echo two'",
+            "c2rust-config config --make --feature default --set clean.dir .",
+        ]
+    );
+}